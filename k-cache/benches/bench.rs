@@ -5,4 +5,6 @@ mod benchmarks;
 
 criterion_main! {
     benchmarks::cache_bench::benches,
+    benchmarks::churn_bench::benches,
+    benchmarks::policy_bench::benches,
 }
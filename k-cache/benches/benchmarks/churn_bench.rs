@@ -0,0 +1,31 @@
+use std::hash::RandomState;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+/// A single cache under a mix of gets, puts and removes, at capacity. Removes leave slots for
+/// the sieve hand to collect, so this covers the bookkeeping of each entry's state.
+pub fn churn(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("churn");
+    group.throughput(Throughput::Elements(1));
+
+    for remove_percent in [0, 10, 30] {
+        group.bench_function(BenchmarkId::new("kcache", remove_percent), |bencher| {
+            let mut cache = k_cache::Cache::<u64, u64, RandomState>::new(RandomState::new(), 8192);
+            bencher.iter(|| {
+                let key = rand::random::<u64>() % 16384;
+                let die = rand::random::<u64>() % 100;
+                if die < remove_percent {
+                    cache.remove(&key);
+                } else if die < 60 {
+                    if cache.get(&key).is_none() {
+                        cache.put(key, key);
+                    }
+                } else {
+                    cache.put(key, key);
+                }
+            })
+        });
+    }
+}
+
+criterion::criterion_group!(benches, churn);
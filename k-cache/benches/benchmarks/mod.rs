@@ -1 +1,3 @@
 pub mod cache_bench;
+pub mod churn_bench;
+pub mod policy_bench;
@@ -0,0 +1,110 @@
+use std::hash::RandomState;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use k_cache::{Cache, DefaultLifecycle, EvictionPolicy, Lru, One, S3Fifo, Sieve};
+
+const KEYS: usize = 100_000;
+const CAPACITY: usize = 5_000;
+const REQUESTS: usize = 1_000_000;
+
+/// Samples keys 0..n with probability proportional to 1 / (rank + 1)^exponent.
+struct Zipf {
+    cumulative: Vec<f64>,
+}
+
+impl Zipf {
+    fn new(n: usize, exponent: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (0..n)
+            .map(|rank| {
+                total += 1.0 / ((rank + 1) as f64).powf(exponent);
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    fn sample(&self) -> u64 {
+        let target = rand::random::<f64>() * self.cumulative[self.cumulative.len() - 1];
+        self.cumulative.partition_point(|&total| total < target) as u64
+    }
+}
+
+/// A request stream: Zipf-distributed reads, optionally interleaved with one-off scans of
+/// keys that are never requested again.
+fn workload(scans: bool) -> Vec<u64> {
+    let zipf = Zipf::new(KEYS, 0.9);
+    let mut next_scan_key = KEYS as u64;
+    let mut requests = Vec::with_capacity(REQUESTS);
+    while requests.len() < REQUESTS {
+        if scans && rand::random::<u64>().is_multiple_of(10_000) {
+            requests.extend(next_scan_key..next_scan_key + 2 * CAPACITY as u64);
+            next_scan_key += 2 * CAPACITY as u64;
+        } else {
+            requests.push(zipf.sample());
+        }
+    }
+    requests.truncate(REQUESTS);
+    requests
+}
+
+fn new_cache<P: EvictionPolicy<u64> + Default>(
+) -> Cache<u64, u64, RandomState, One, DefaultLifecycle, P> {
+    Cache::new(RandomState::new(), CAPACITY)
+}
+
+/// A get, and a put upon a miss. Returns whether the get hit.
+fn request<P: EvictionPolicy<u64>>(
+    cache: &mut Cache<u64, u64, RandomState, One, DefaultLifecycle, P>,
+    key: u64,
+) -> bool {
+    if cache.get(&key).is_some() {
+        true
+    } else {
+        cache.put(key, key);
+        false
+    }
+}
+
+fn hit_ratio<P: EvictionPolicy<u64> + Default>(requests: &[u64]) -> f64 {
+    let mut cache = new_cache::<P>();
+    let hits = requests
+        .iter()
+        .filter(|&&key| request(&mut cache, key))
+        .count();
+    hits as f64 / requests.len() as f64
+}
+
+fn bench_policy<P: EvictionPolicy<u64> + Default>(
+    criterion: &mut Criterion,
+    name: &str,
+    workload_name: &str,
+    requests: &[u64],
+) {
+    // Criterion measures time, so print the hit ratio alongside it.
+    println!(
+        "{workload_name}/{name}: hit ratio {:.2}%",
+        100.0 * hit_ratio::<P>(requests)
+    );
+
+    let mut group = criterion.benchmark_group(format!("policy-{workload_name}"));
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(BenchmarkId::new(name, CAPACITY), |bencher| {
+        let mut cache = new_cache::<P>();
+        let mut requests = requests.iter().cycle();
+        bencher.iter(|| request(&mut cache, *requests.next().unwrap()))
+    });
+    group.finish();
+}
+
+/// Each policy on the same request streams, reporting its hit ratio and time per request.
+pub fn policies(criterion: &mut Criterion) {
+    for (workload_name, scans) in [("zipf", false), ("zipf-with-scans", true)] {
+        let requests = workload(scans);
+        bench_policy::<Sieve<u64>>(criterion, "sieve", workload_name, &requests);
+        bench_policy::<S3Fifo<u64>>(criterion, "s3-fifo", workload_name, &requests);
+        bench_policy::<Lru<u64>>(criterion, "lru", workload_name, &requests);
+    }
+}
+
+criterion::criterion_group!(benches, policies);
@@ -0,0 +1,59 @@
+//! Reports the heap bytes the cache spends per entry. Run with `cargo bench --bench memory`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hash::RandomState,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use k_cache::EvictionPolicy;
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ENTRIES: u64 = 1 << 20;
+
+fn main() {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut cache = k_cache::Cache::<u64, u64, RandomState>::new(RandomState::new(), usize::MAX);
+    for i in 0..ENTRIES {
+        cache.put(i, i);
+    }
+    let filled = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!(
+        "filled:  {:>6.1} bytes per entry, {} entries",
+        filled as f64 / ENTRIES as f64,
+        ENTRIES
+    );
+
+    // Removed entries leave their keys in the sieve pool until the hand collects them.
+    for i in (0..ENTRIES).step_by(2) {
+        cache.remove(&i);
+    }
+    let churned = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!(
+        "removed: {:>6.1} bytes per remaining entry, {} entries",
+        churned as f64 / (ENTRIES / 2) as f64,
+        ENTRIES / 2
+    );
+    println!(
+        "estimated overhead: {} bytes per entry",
+        k_cache::entry_overhead_bytes::<u64, u64>(k_cache::Sieve::<u64>::default().slot_bytes())
+    );
+}
@@ -1,25 +1,100 @@
 use std::hash::Hash;
 use std::marker::PhantomData;
-use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Instant;
 use std::{
     borrow::Borrow,
-    collections::{HashMap, VecDeque},
+    collections::{BinaryHeap, HashMap},
     hash::BuildHasher,
 };
 
+use crate::entry::Entry;
+use crate::expiration::Expiration;
+use crate::sieve::Sieve;
+use crate::stats::{SharedStats, Stats};
+use crate::{EvictionPolicy, Victim};
+
+/// Weighs entries against the cache's max_weight. Keys, values and each entry's bookkeeping
+/// are charged separately, because a key can outlive its value: after a removal, some eviction
+/// policies keep the key, still charged, until they collect it.
 pub trait Weigher<K, V> {
-    fn weigh(_k: &K, _v: &V) -> usize {
+    fn weigh_key(_key: &K) -> usize {
+        0
+    }
+
+    fn weigh_value(_value: &V) -> usize {
         1
     }
+
+    /// A fixed charge for each entry, released when the entry is removed. policy_slot_bytes is
+    /// the cache's eviction policy's EvictionPolicy::slot_bytes. For weights in bytes, see
+    /// entry_overhead_bytes.
+    fn entry_overhead(_policy_slot_bytes: usize) -> usize {
+        0
+    }
+
+    /// A fixed charge for each expiration deadline waiting in the cache's queue, released when
+    /// the deadline comes due. For weights in bytes, see expiration_overhead_bytes.
+    fn expiration_overhead() -> usize {
+        0
+    }
+}
+
+/// An estimate of the bytes the cache spends on each entry besides the key's and value's own
+/// heap allocations: the map slot and the eviction policy's slot.
+pub fn entry_overhead_bytes<K, V>(policy_slot_bytes: usize) -> usize {
+    // One control byte per hash map slot.
+    size_of::<(K, MapEntry<V>)>() + 1 + policy_slot_bytes
+}
+
+/// An estimate of the bytes the cache spends on each queued expiration deadline.
+pub fn expiration_overhead_bytes<K>() -> usize {
+    size_of::<Expiration<K>>()
 }
 
 #[derive(Debug, Clone)]
 pub struct One;
 impl<K, V> Weigher<K, V> for One {}
 
+/// The cache operation that fired a lifecycle hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// put or put_with_expiration.
+    Put,
+    /// An insert or removal through an Entry, including SegmentedCache's get_or_insert_with
+    /// and compute.
+    Entry,
+    /// remove or remove_if.
+    Remove,
+    /// retain filtered the entry out.
+    Retain,
+    /// clear.
+    Clear,
+    /// purge_expired.
+    PurgeExpired,
+    /// shrink, after the max weight was lowered.
+    Shrink,
+}
+
+/// Hooks for changes to a cache's entries. They run under the cache's lock, so keep them short.
+///
+/// Hooks get values the cache drops by value, and values the caller keeps by reference.
 pub trait Lifecycle<K, V> {
-    fn on_eviction(&self, _key: K, _value: V) {}
+    /// Called when a key that was absent is inserted.
+    fn on_insert(&self, _key: &K, _value: &V, _cause: Cause) {}
+
+    /// Called when a live entry's value is replaced. Replacing an expired entry is an expiry
+    /// followed by an insert instead.
+    fn on_replace(&self, _key: &K, _old_value: V, _new_value: &V, _cause: Cause) {}
+
+    /// Called when a live entry is removed on request.
+    fn on_remove(&self, _key: &K, _value: &V, _cause: Cause) {}
+
+    /// Called when an entry is evicted to make room for another, or to shrink the cache.
+    fn on_eviction(&self, _key: K, _value: V, _cause: Cause) {}
+
+    /// Called when an entry is removed because its expiration deadline passed.
+    fn on_expiry(&self, _key: K, _value: V, _cause: Cause) {}
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -27,89 +102,192 @@ pub struct DefaultLifecycle;
 impl<K, V> Lifecycle<K, V> for DefaultLifecycle {}
 
 #[derive(Debug)]
-struct SieveEntry<D> {
-    data: D,
-    visited: Arc<AtomicBool>,
+struct MapEntry<V> {
+    data: V,
+    /// The key's slot in the eviction policy.
+    slot: usize,
+    expires_at: Option<Instant>,
+}
+
+impl<V> MapEntry<V> {
+    /// Only reads the clock for entries that can expire.
+    fn is_live(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| Instant::now() < expires_at)
+    }
 }
 
 #[derive(Debug)]
-pub struct Cache<K, V, S, W: Weigher<K, V> = One, L: Lifecycle<K, V> = DefaultLifecycle> {
-    map: HashMap<K, SieveEntry<V>, S>,
-    sieve_pool: VecDeque<SieveEntry<K>>,
-    sieve_hand: usize,
+pub struct Cache<
+    K,
+    V,
+    S,
+    W: Weigher<K, V> = One,
+    L: Lifecycle<K, V> = DefaultLifecycle,
+    P: EvictionPolicy<K> = Sieve<K>,
+> {
+    map: HashMap<K, MapEntry<V>, S>,
+    policy: P,
+    expirations: BinaryHeap<Expiration<K>>,
     max_weight: usize,
     weight: usize,
+    /// W::entry_overhead for this cache's policy.
+    entry_overhead: usize,
+    stats: Arc<SharedStats>,
     lifecycle: L,
     _phantom: PhantomData<W>,
 }
 
-impl<K, V, S, W, L> Cache<K, V, S, W, L>
+impl<K, V, S, W, L, P> Cache<K, V, S, W, L, P>
 where
     K: Eq + Hash + Clone,
     S: BuildHasher,
     W: Weigher<K, V>,
     L: Lifecycle<K, V> + Default,
+    P: EvictionPolicy<K> + Default,
 {
     pub fn new(hasher: S, max_weight: usize) -> Self {
-        Self {
-            map: HashMap::with_hasher(hasher),
-            sieve_pool: VecDeque::new(),
-            sieve_hand: 0,
-            max_weight,
-            weight: 0,
-            lifecycle: Default::default(),
-            _phantom: PhantomData,
-        }
+        Self::new_with_policy(hasher, max_weight, Default::default(), Default::default())
     }
 }
 
-impl<K, V, S, W, L> Cache<K, V, S, W, L>
+impl<K, V, S, W, L, P> Cache<K, V, S, W, L, P>
 where
     K: Eq + Hash + Clone,
     S: BuildHasher,
     W: Weigher<K, V>,
     L: Lifecycle<K, V>,
+    P: EvictionPolicy<K> + Default,
 {
     pub fn new_with_lifecycle(hasher: S, max_weight: usize, lifecycle: L) -> Self {
+        Self::new_with_policy(hasher, max_weight, lifecycle, Default::default())
+    }
+}
+
+impl<K, V, S, W, L, P> Cache<K, V, S, W, L, P>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
+    /// A cache that evicts with policy, which should not be tracking any keys yet.
+    pub fn new_with_policy(hasher: S, max_weight: usize, lifecycle: L, policy: P) -> Self {
         Self {
             map: HashMap::with_hasher(hasher),
-            sieve_pool: VecDeque::new(),
-            sieve_hand: 0,
+            entry_overhead: W::entry_overhead(policy.slot_bytes()),
+            policy,
+            expirations: BinaryHeap::new(),
             max_weight,
             weight: 0,
+            stats: Arc::new(SharedStats::new(max_weight)),
             lifecycle,
             _phantom: PhantomData,
         }
     }
 
     pub fn put(&mut self, key: K, value: V) {
-        let new_entry_weight = self.make_room_for(&key, &value);
-        self.weight += new_entry_weight;
+        self.insert(key, value, None, Cause::Put)
+    }
+
+    /// Put an entry that is treated as absent once expires_at has passed.
+    pub fn put_with_expiration(&mut self, key: K, value: V, expires_at: Instant) {
+        self.insert(key, value, Some(expires_at), Cause::Put)
+    }
+
+    /// Look up key once, for a get followed by an insert or a removal. A live entry counts as
+    /// a hit, and anything else as a miss.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S, W, L, P> {
+        if self.get(&key).is_some() {
+            Entry::occupied(self, key)
+        } else {
+            Entry::vacant(self, key)
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V, expires_at: Option<Instant>, cause: Cause) {
+        let value_weight = W::weigh_value(&value);
+        let expiration_weight = if expires_at.is_some() {
+            W::expiration_overhead()
+        } else {
+            0
+        };
+        let new_entry_weight = W::weigh_key(&key) + value_weight + self.entry_overhead;
+        if self.map.contains_key(&key) {
+            self.make_room_for(value_weight + expiration_weight, cause);
+            if !self.map.contains_key(&key) {
+                // Making room evicted or expired the entry being replaced, so this inserts a
+                // new entry after all, and must make room for the rest of it.
+                self.make_room_for(new_entry_weight + expiration_weight, cause);
+            }
+        } else {
+            self.make_room_for(new_entry_weight + expiration_weight, cause);
+        }
+        let expiration = expires_at.map(|expires_at| Expiration {
+            expires_at,
+            key: key.clone(),
+        });
 
         match self.map.entry(key.clone()) {
             std::collections::hash_map::Entry::Occupied(mut occupied_entry) => {
-                let replaced_weight = W::weigh(&key, &occupied_entry.get().data);
-                self.weight -= replaced_weight; // already added the new entry weight
+                let replaced_weight = W::weigh_value(&occupied_entry.get().data);
+                self.weight = self.weight + value_weight - replaced_weight;
 
-                occupied_entry.get_mut().data = value;
-                occupied_entry
-                    .get_mut()
-                    .visited
-                    .store(true, std::sync::atomic::Ordering::Relaxed);
+                let entry = occupied_entry.get_mut();
+                let was_live = entry.is_live();
+                let replaced = std::mem::replace(&mut entry.data, value);
+                entry.expires_at = expires_at;
+                self.policy.update(entry.slot);
+                if was_live {
+                    self.stats.update();
+                    self.lifecycle
+                        .on_replace(&key, replaced, &entry.data, cause);
+                } else {
+                    self.stats.expiration();
+                    self.stats.insert();
+                    self.lifecycle.on_expiry(key.clone(), replaced, cause);
+                    self.lifecycle.on_insert(&key, &entry.data, cause);
+                }
             }
             std::collections::hash_map::Entry::Vacant(vacant_entry) => {
-                // it's still possible to get spurious evictions in here. I should replace the dirty atomicbool
-                // with a tri-state int to track deletion separately from visitation. That could also allow some
-                // stupid bitwise tricks to remember insertions and possibly eagerly sieve out unpopular, though
-                // technically accessed, entries.
-                let visited = Arc::new(AtomicBool::new(true));
-                vacant_entry.insert(SieveEntry {
+                self.weight += new_entry_weight;
+                self.lifecycle.on_insert(&key, &value, cause);
+                let slot = self.policy.insert(key);
+                vacant_entry.insert(MapEntry {
                     data: value,
-                    visited: visited.clone(),
+                    slot,
+                    expires_at,
                 });
-                self.sieve_pool.push_back(SieveEntry { data: key, visited });
+                self.stats.insert();
             }
         }
+
+        if let Some(expiration) = expiration {
+            self.schedule_expiration(expiration);
+        }
+        self.publish_sizes();
+    }
+
+    fn schedule_expiration(&mut self, expiration: Expiration<K>) {
+        self.expirations.push(expiration);
+        self.weight += W::expiration_overhead();
+        // Stale expirations are only dropped when they come due. If keys are rewritten with long
+        // deadlines they can pile up, so rebuild the queue from the live entries now and then.
+        if 2 * self.map.len() + 64 < self.expirations.len() {
+            self.release(self.expirations.len() * W::expiration_overhead());
+            self.expirations = self
+                .map
+                .iter()
+                .filter_map(|(key, entry)| {
+                    entry.expires_at.map(|expires_at| Expiration {
+                        expires_at,
+                        key: key.clone(),
+                    })
+                })
+                .collect();
+            self.weight += self.expirations.len() * W::expiration_overhead();
+        }
     }
 
     pub fn get<Q>(&self, key: &Q) -> Option<&V>
@@ -118,71 +296,329 @@ where
         Q: Hash + Eq + ?Sized,
     {
         match self.map.get(key) {
-            Some(entry) => {
-                entry
-                    .visited
-                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            Some(entry) if entry.is_live() => {
+                self.policy.visit(entry.slot);
+                self.stats.hit();
                 Some(&entry.data)
             }
-            None => None,
+            _ => {
+                self.stats.miss();
+                None
+            }
         }
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        match self.map.remove(key) {
-            Some(removed) => {
-                // Note that this under-counts memory by accounting for the key size
-                // up front. The key is still in the sieve list. Hopefully it will be
-                // reclaimed soon. I could split the key and value weighers to do a
-                // better accounting job.
-                let removed_weight = W::weigh(key, &removed.data);
-                match self.weight.checked_sub(removed_weight) {
-                    Some(new_weight) => self.weight = new_weight,
-                    None => {
-                        log::error!("weight underflow");
-                        self.weight = 0;
-                    }
-                };
-                Some(removed.data)
-            }
-            None => {
-                log::debug!("garbage collecting sieve entry as {}", self.sieve_hand);
-                None
+    /// Whether key has a live entry. Unlike get, it is not counted and does not visit the entry.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get(key).is_some_and(MapEntry::is_live)
+    }
+
+    /// The expiration deadline of a live entry, if it has one.
+    pub fn expiration<Q>(&self, key: &Q) -> Option<Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map
+            .get(key)
+            .filter(|entry| entry.is_live())
+            .and_then(|entry| entry.expires_at)
+    }
+
+    /// The value of a live entry, without counting a hit or miss or visiting the entry. For
+    /// reads the caller makes on its own behalf, like the read in a read-modify-write.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map
+            .get(key)
+            .filter(|entry| entry.is_live())
+            .map(|entry| &entry.data)
+    }
+
+    /// The value of an entry, whether or not it is live. Like peek, it is not counted, and does
+    /// not visit the entry.
+    pub(crate) fn peek_present<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get(key).map(|entry| &entry.data)
+    }
+
+    /// Remove an entry through an Entry, whether or not it is live, counting it as a removal.
+    pub(crate) fn remove_present<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, removed) = self.take(key)?;
+        self.publish_sizes();
+        self.stats.removal();
+        self.lifecycle.on_remove(&key, &removed.data, Cause::Entry);
+        Some(removed.data)
+    }
+
+    /// Expired entries are absent: they are released, but not returned.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_because(key, Cause::Remove)
+    }
+
+    fn remove_because<Q>(&mut self, key: &Q, cause: Cause) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, removed) = self.take(key)?;
+        self.publish_sizes();
+        if removed.is_live() {
+            self.stats.removal();
+            self.lifecycle.on_remove(&key, &removed.data, cause);
+            Some(removed.data)
+        } else {
+            self.stats.expiration();
+            self.lifecycle.on_expiry(key, removed.data, cause);
+            None
+        }
+    }
+
+    /// Remove the entry for key only if predicate returns true for its current value.
+    pub fn remove_if<Q>(&mut self, key: &Q, predicate: impl FnOnce(&V) -> bool) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.map.get(key) {
+            Some(entry) if entry.is_live() && predicate(&entry.data) => self.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Remove every entry whose expiration deadline has passed. Returns the number removed.
+    pub fn purge_expired(&mut self) -> usize {
+        self.purge_expired_because(Cause::PurgeExpired, usize::MAX)
+    }
+
+    /// Remove up to max_purged expired entries. Returns the number removed.
+    fn purge_expired_because(&mut self, cause: Cause, max_purged: usize) -> usize {
+        let now = Instant::now();
+        let mut purged = 0;
+        let mut popped = false;
+        while purged < max_purged
+            && self
+                .expirations
+                .peek()
+                .is_some_and(|expiration| expiration.expires_at <= now)
+        {
+            let expiration = self.expirations.pop().expect("peeked expiration exists");
+            self.release(W::expiration_overhead());
+            popped = true;
+            // The entry may have been replaced or removed since this expiration was scheduled.
+            let is_current = self
+                .map
+                .get(&expiration.key)
+                .is_some_and(|entry| entry.expires_at == Some(expiration.expires_at));
+            if is_current {
+                if let Some((key, removed)) = self.take(&expiration.key) {
+                    self.stats.expiration();
+                    self.lifecycle.on_expiry(key, removed.data, cause);
+                    purged += 1;
+                }
             }
         }
+        if popped {
+            self.publish_sizes();
+        }
+        purged
+    }
+
+    /// The live entries, in no particular order. Unlike get, this does not count or visit them.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .filter(|(_, entry)| entry.is_live())
+            .map(|(key, entry)| (key, &entry.data))
+    }
+
+    /// Keep only the live entries for which f returns true. The others are removed like remove
+    /// would, and expired entries are removed as expirations. f only sees live entries.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let doomed: Vec<K> = self
+            .map
+            .iter()
+            .filter(|(key, entry)| !entry.is_live() || !f(key, &entry.data))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in doomed {
+            self.remove_because(&key, Cause::Retain);
+        }
     }
 
-    fn make_room_for(&mut self, key: &K, value: &V) -> usize {
-        let entry_weight = W::weigh(key, value);
-        while self.max_weight < self.weight + entry_weight {
-            let sieve_entry = &mut self.sieve_pool[self.sieve_hand];
-            let visited = sieve_entry
-                .visited
-                .swap(false, std::sync::atomic::Ordering::Relaxed);
-            if visited {
-                self.sieve_hand = (self.sieve_hand + 1) % self.sieve_pool.len();
+    /// Remove every entry. Live entries count as removals, and expired entries as expirations.
+    /// Unlike remove, the keys are released right away, since the policy forgets them too.
+    pub fn clear(&mut self) {
+        for (key, entry) in self.map.drain() {
+            if entry.is_live() {
+                self.stats.removal();
+                self.lifecycle.on_remove(&key, &entry.data, Cause::Clear);
             } else {
-                let sieve_key_entry = self
-                    .sieve_pool
-                    .swap_remove_back(self.sieve_hand)
-                    .expect("the index must be present");
-                let removed = self.remove(&sieve_key_entry.data);
-                if let Some(removed_value) = removed {
-                    self.lifecycle
-                        .on_eviction(sieve_key_entry.data, removed_value);
-                } else {
-                    // This can happen when the entry was already removed. It's not an eviction,
-                    // but a clean up of the sieve list. The value was already released - we can
-                    // simply drop the key.
-                    log::debug!("garbage collecting sieve entry at {}", self.sieve_hand);
-                }
+                self.stats.expiration();
+                self.lifecycle.on_expiry(key, entry.data, Cause::Clear);
+            }
+        }
+        self.policy.clear();
+        self.expirations.clear();
+        self.weight = 0;
+        self.publish_sizes();
+    }
 
-                if self.sieve_hand == self.sieve_pool.len() {
-                    self.sieve_hand = 0;
+    /// The total weight of the entries, including expired entries that are not yet purged.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// The number of entries, including expired entries that are not yet purged.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The weight the cache holds itself to.
+    pub fn max_weight(&self) -> usize {
+        self.max_weight
+    }
+
+    /// Change the max weight. Raising it takes effect right away. Lowering it evicts nothing by
+    /// itself: until the cache is back under the new max weight, puts make room for themselves
+    /// without growing the cache, and shrink evicts the excess a batch at a time.
+    pub fn set_max_weight(&mut self, max_weight: usize) {
+        self.max_weight = max_weight;
+        self.stats.set_max_weight(max_weight);
+    }
+
+    /// Evict entries through the policy while the cache is heavier than its max weight,
+    /// up to max_evictions of them. Expired entries go first, and count toward max_evictions.
+    /// Returns the number of entries removed; 0 once the cache fits.
+    pub fn shrink(&mut self, max_evictions: usize) -> usize {
+        if self.weight <= self.max_weight {
+            return 0;
+        }
+        let mut removed = self.purge_expired_because(Cause::Shrink, max_evictions);
+        while self.max_weight < self.weight && removed < max_evictions {
+            match self.evict_next(Cause::Shrink) {
+                Some(true) => removed += 1,
+                Some(false) => {}
+                None => break,
+            }
+        }
+        self.publish_sizes();
+        removed
+    }
+
+    /// Statistics about this cache's use. Cheap to call; see also shared_stats.
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// The statistics are updated in place, so a holder can read them without this cache.
+    pub(crate) fn shared_stats(&self) -> Arc<SharedStats> {
+        self.stats.clone()
+    }
+
+    fn publish_sizes(&self) {
+        self.stats
+            .set_sizes(self.weight, self.map.len(), self.policy.len());
+    }
+
+    fn take<Q>(&mut self, key: &Q) -> Option<(K, MapEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, removed) = self.unmap(key)?;
+        // The policy may keep the key, still charged, until it collects it.
+        if let Some(key) = self.policy.remove(removed.slot) {
+            self.release(W::weigh_key(&key));
+        }
+        Some((key, removed))
+    }
+
+    /// Remove an entry from the map, leaving its slot in the policy alone.
+    fn unmap<Q>(&mut self, key: &Q) -> Option<(K, MapEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, removed) = self.map.remove_entry(key)?;
+        self.release(W::weigh_value(&removed.data) + self.entry_overhead);
+        Some((key, removed))
+    }
+
+    fn release(&mut self, weight: usize) {
+        match self.weight.checked_sub(weight) {
+            Some(new_weight) => self.weight = new_weight,
+            None => {
+                log::error!("weight underflow");
+                self.weight = 0;
+            }
+        };
+    }
+
+    fn make_room_for(&mut self, entry_weight: usize, cause: Cause) {
+        // After the max weight is lowered the cache may be over it. Puts then only make room
+        // for themselves, and shrink evicts the rest, so no one put pays for the whole excess.
+        let limit = self.max_weight.max(self.weight);
+        if limit < self.weight + entry_weight {
+            // Expired entries go first, whether or not they were visited.
+            self.purge_expired_because(cause, usize::MAX);
+        }
+        while limit < self.weight + entry_weight {
+            if self.evict_next(cause).is_none() {
+                // The entry is heavier than the whole cache. There is nothing left to evict.
+                break;
+            }
+        }
+    }
+
+    /// Have the policy give up a slot, evicting or collecting it. Returns whether an entry
+    /// was evicted, or None when the policy tracks no keys.
+    fn evict_next(&mut self, cause: Cause) -> Option<bool> {
+        let map = &mut self.map;
+        let victim = self.policy.evict(|key, slot| {
+            map.get_mut(key)
+                .expect("live slots must have a map entry")
+                .slot = slot
+        })?;
+        match victim {
+            Victim::Evict(key) => {
+                self.release(W::weigh_key(&key));
+                if let Some((key, removed)) = self.unmap(&key) {
+                    self.stats.eviction();
+                    self.lifecycle.on_eviction(key, removed.data, cause);
                 }
+                Some(true)
+            }
+            Victim::Collect(key) => {
+                // The entry was already removed, so this is not an eviction. Its value was
+                // already released; only the key was still charged.
+                self.release(W::weigh_key(&key));
+                log::debug!("garbage collecting a removed key");
+                Some(false)
             }
         }
-        entry_weight
     }
 }
 
@@ -201,6 +637,617 @@ mod test {
         assert_eq!(cache.get("key1"), Some(&"value2".to_string()));
         assert_eq!(cache.weight, 1);
         assert_eq!(cache.map.len(), 1);
-        assert_eq!(cache.sieve_pool.len(), 1);
+        assert_eq!(cache.policy.len(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut cache: Cache<String, String, RandomState> = Cache::new(RandomState::new(), 100);
+        cache.put("key1".to_string(), "value1".to_string());
+        assert_eq!(cache.remove("key1"), Some("value1".to_string()));
+        assert_eq!(cache.get("key1"), None);
+        assert_eq!(cache.remove("key1"), None);
+        assert_eq!(cache.weight, 0);
+        assert_eq!(cache.map.len(), 0);
+    }
+
+    #[test]
+    fn test_remove_if() {
+        let mut cache: Cache<String, String, RandomState> = Cache::new(RandomState::new(), 100);
+        cache.put("key1".to_string(), "value1".to_string());
+        assert_eq!(cache.remove_if("key1", |v| v == "value2"), None);
+        assert_eq!(cache.get("key1"), Some(&"value1".to_string()));
+        assert_eq!(
+            cache.remove_if("key1", |v| v == "value1"),
+            Some("value1".to_string())
+        );
+        assert_eq!(cache.get("key1"), None);
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct Counts {
+        evicted: Arc<std::sync::atomic::AtomicUsize>,
+        expired: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    impl Lifecycle<String, String> for Counts {
+        fn on_eviction(&self, _key: String, _value: String, _cause: Cause) {
+            self.evicted
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        fn on_expiry(&self, _key: String, _value: String, _cause: Cause) {
+            self.expired
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Records every hook as a line, like "insert a=1 Put".
+    #[derive(Debug, Default, Clone)]
+    struct Events(Arc<std::sync::Mutex<Vec<String>>>);
+    impl Events {
+        fn record(&self, event: String) {
+            self.0
+                .lock()
+                .expect("mutex must not be poisoned")
+                .push(event);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().expect("mutex must not be poisoned"))
+        }
+    }
+    impl Lifecycle<String, String> for Events {
+        fn on_insert(&self, key: &String, value: &String, cause: Cause) {
+            self.record(format!("insert {key}={value} {cause:?}"));
+        }
+
+        fn on_replace(&self, key: &String, old_value: String, new_value: &String, cause: Cause) {
+            self.record(format!("replace {key}={old_value}->{new_value} {cause:?}"));
+        }
+
+        fn on_remove(&self, key: &String, value: &String, cause: Cause) {
+            self.record(format!("remove {key}={value} {cause:?}"));
+        }
+
+        fn on_eviction(&self, key: String, value: String, cause: Cause) {
+            self.record(format!("evict {key}={value} {cause:?}"));
+        }
+
+        fn on_expiry(&self, key: String, value: String, cause: Cause) {
+            self.record(format!("expire {key}={value} {cause:?}"));
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let events = Events::default();
+        let mut cache: Cache<String, String, RandomState, One, Events> =
+            Cache::new_with_lifecycle(RandomState::new(), 2, events.clone());
+        cache.put("a".to_string(), "1".to_string());
+        cache.put("a".to_string(), "2".to_string());
+        cache.put_with_expiration("b".to_string(), "1".to_string(), Instant::now());
+        cache.put("b".to_string(), "2".to_string());
+        assert_eq!(
+            events.take(),
+            [
+                "insert a=1 Put",
+                "replace a=1->2 Put",
+                "insert b=1 Put",
+                "expire b=1 Put",
+                "insert b=2 Put",
+            ]
+        );
+
+        cache.put("c".to_string(), "1".to_string());
+        cache.entry("c".to_string()).or_insert("unused".to_string());
+        assert_eq!(cache.remove("c"), Some("1".to_string()));
+        assert_eq!(cache.remove("c"), None);
+        assert_eq!(
+            events.take(),
+            // Replacing a visited it, so the hand spared it and evicted b, which was never read.
+            ["evict b=2 Put", "insert c=1 Put", "remove c=1 Remove"]
+        );
+
+        cache.put_with_expiration("d".to_string(), "1".to_string(), Instant::now());
+        cache.purge_expired();
+        cache.entry("e".to_string()).or_insert("1".to_string());
+        cache.retain(|key, _| key != "e");
+        cache.put("f".to_string(), "1".to_string());
+        if let Entry::Occupied(entry) = cache.entry("f".to_string()) {
+            entry.insert("2".to_string());
+        }
+        assert_eq!(
+            events.take(),
+            [
+                "insert d=1 Put",
+                "expire d=1 PurgeExpired",
+                "insert e=1 Entry",
+                "remove e=1 Retain",
+                "insert f=1 Put",
+                // A replacement makes room for its whole value.
+                "evict a=2 Entry",
+                "replace f=1->2 Entry",
+            ]
+        );
+
+        cache.clear();
+        assert_eq!(events.take(), ["remove f=2 Clear"]);
+    }
+
+    #[test]
+    fn test_expiration() {
+        let counts = Counts::default();
+        let mut cache: Cache<String, String, RandomState, One, Counts> =
+            Cache::new_with_lifecycle(RandomState::new(), 100, counts.clone());
+        let later = Instant::now() + std::time::Duration::from_secs(60);
+        cache.put_with_expiration("live".to_string(), "value".to_string(), later);
+        cache.put_with_expiration("expired".to_string(), "value".to_string(), Instant::now());
+        assert_eq!(cache.get("live"), Some(&"value".to_string()));
+        assert_eq!(cache.get("expired"), None);
+        assert_eq!(cache.expiration("live"), Some(later));
+        assert_eq!(cache.expiration("expired"), None);
+        assert_eq!(cache.remove_if("expired", |_| true), None);
+
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.weight, 1);
+        assert_eq!(cache.map.len(), 1);
+        assert_eq!(counts.expired.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        // A put without expiration clears the deadline.
+        cache.put_with_expiration("live".to_string(), "value".to_string(), Instant::now());
+        cache.put("live".to_string(), "forever".to_string());
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.get("live"), Some(&"forever".to_string()));
+    }
+
+    #[test]
+    fn test_peek_is_not_a_read() {
+        let mut cache: Cache<&str, u64, RandomState, One, DefaultLifecycle, crate::Lru<&str>> =
+            Cache::new(RandomState::new(), 3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put_with_expiration("c", 3, Instant::now());
+        assert_eq!(cache.peek("a"), Some(&1));
+        assert_eq!(cache.peek("c"), None, "expired entries are absent");
+        assert_eq!(cache.peek("absent"), None);
+        assert_eq!((cache.stats().hits, cache.stats().misses), (0, 0));
+
+        // Peeking a did not make it recently used, so it is evicted before b.
+        cache.purge_expired();
+        cache.put("d", 4);
+        cache.put("e", 5);
+        assert_eq!(cache.peek("a"), None);
+        assert_eq!(cache.peek("b"), Some(&2));
+    }
+
+    #[test]
+    fn test_retain_and_clear() {
+        let counts = Counts::default();
+        let mut cache: Cache<String, String, RandomState, One, Counts> =
+            Cache::new_with_lifecycle(RandomState::new(), 100, counts.clone());
+        for key in ["a1", "a2", "b1"] {
+            cache.put(key.to_string(), "value".to_string());
+        }
+        cache.put_with_expiration("a3".to_string(), "value".to_string(), Instant::now());
+
+        let mut seen = Vec::new();
+        cache.retain(|key, _| {
+            seen.push(key.clone());
+            !key.starts_with('a')
+        });
+        seen.sort();
+        assert_eq!(seen, ["a1", "a2", "b1"]);
+        assert_eq!(
+            cache.iter().collect::<Vec<_>>(),
+            [(&"b1".to_string(), &"value".to_string())]
+        );
+        assert_eq!(cache.weight(), 1);
+        assert_eq!(cache.stats().removals, 2);
+        assert_eq!(counts.expired.load(std::sync::atomic::Ordering::Relaxed), 1);
+        // The removed keys wait in the sieve pool like after remove.
+        assert_eq!(cache.policy.len(), 4);
+
+        cache.put_with_expiration("b2".to_string(), "value".to_string(), Instant::now());
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.weight(), 0);
+        assert_eq!(cache.policy.len(), 0);
+        assert_eq!(cache.stats().removals, 3);
+        assert_eq!(counts.expired.load(std::sync::atomic::Ordering::Relaxed), 2);
+        assert_eq!(cache.purge_expired(), 0);
+
+        cache.put("c".to_string(), "value".to_string());
+        assert_eq!(cache.get("c"), Some(&"value".to_string()));
+        assert_eq!(cache.stats().policy_length, 1);
+    }
+
+    #[test]
+    fn test_expired_entries_are_evicted_first() {
+        let counts = Counts::default();
+        let mut cache: Cache<String, String, RandomState, One, Counts> =
+            Cache::new_with_lifecycle(RandomState::new(), 2, counts.clone());
+        cache.put("visited".to_string(), "value".to_string());
+        cache.put_with_expiration("expired".to_string(), "value".to_string(), Instant::now());
+        cache.put("new".to_string(), "value".to_string());
+
+        assert_eq!(cache.get("visited"), Some(&"value".to_string()));
+        assert_eq!(cache.get("new"), Some(&"value".to_string()));
+        assert_eq!(counts.expired.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(counts.evicted.load(std::sync::atomic::Ordering::Relaxed), 0);
+        assert_eq!(cache.stats().expirations, 1);
+        assert_eq!(cache.stats().evictions, 0);
+
+        cache.put("another".to_string(), "value".to_string());
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.weight(), 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut cache: Cache<String, String, RandomState> = Cache::new(RandomState::new(), 2);
+        cache.put("a".to_string(), "value".to_string());
+        cache.put("a".to_string(), "updated".to_string());
+        cache.put("b".to_string(), "value".to_string());
+        assert_eq!(cache.get("a"), Some(&"updated".to_string()));
+        assert_eq!(cache.get("absent"), None);
+        assert_eq!(cache.remove("b"), Some("value".to_string()));
+        cache.put("c".to_string(), "value".to_string());
+        cache.put("d".to_string(), "value".to_string());
+
+        assert_eq!(
+            cache.stats(),
+            Stats {
+                hits: 1,
+                misses: 1,
+                inserts: 4,
+                updates: 1,
+                removals: 1,
+                evictions: 1,
+                expirations: 0,
+                weight: 2,
+                max_weight: 2,
+                entries: 2,
+                // b's removed slot was collected as the hand passed it, without an eviction.
+                policy_length: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_max_weight() {
+        let mut cache: Cache<u64, u64, RandomState> = Cache::new(RandomState::new(), 10);
+        for key in 0..10 {
+            cache.put(key, key);
+        }
+        cache.set_max_weight(4);
+        assert_eq!(cache.weight(), 10);
+        assert_eq!(cache.stats().max_weight, 4);
+
+        // While over the max weight, a put evicts only enough to make room for itself.
+        cache.put(10, 10);
+        assert_eq!(cache.weight(), 10);
+        assert_eq!(cache.stats().evictions, 1);
+
+        assert_eq!(cache.shrink(2), 2);
+        assert_eq!(cache.weight(), 8);
+        assert_eq!(cache.shrink(100), 4);
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.shrink(100), 0);
+        assert_eq!(cache.stats().evictions, 7);
+        assert_eq!(cache.stats().entries, 4);
+
+        cache.set_max_weight(8);
+        for key in 11..15 {
+            cache.put(key, key);
+        }
+        assert_eq!(cache.weight(), 8);
+        assert_eq!(cache.stats().evictions, 7);
+    }
+
+    #[test]
+    fn test_shrink_caps_expirations() {
+        let mut cache: Cache<u64, u64, RandomState> = Cache::new(RandomState::new(), 10);
+        for key in 0..10 {
+            cache.put_with_expiration(key, key, Instant::now());
+        }
+        cache.set_max_weight(4);
+
+        assert_eq!(cache.shrink(2), 2);
+        assert_eq!(cache.stats().expirations, 2);
+        assert_eq!(cache.stats().entries, 8);
+        assert_eq!(cache.shrink(100), 8);
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.shrink(100), 0);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[derive(Debug, Clone)]
+    struct Lengths;
+    impl Weigher<String, String> for Lengths {
+        fn weigh_key(key: &String) -> usize {
+            key.len()
+        }
+
+        fn weigh_value(value: &String) -> usize {
+            value.len()
+        }
+
+        fn entry_overhead(_policy_slot_bytes: usize) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn test_removed_keys_stay_charged() {
+        let mut cache: Cache<String, String, RandomState, Lengths> =
+            Cache::new(RandomState::new(), 10);
+        cache.put("ab".to_string(), "xyz".to_string());
+        assert_eq!(cache.weight(), 6);
+        cache.put("ab".to_string(), "x".to_string());
+        assert_eq!(cache.weight(), 4);
+
+        cache.remove("ab");
+        // The key waits in the sieve pool until the hand collects it.
+        assert_eq!(cache.weight(), 2);
+        assert_eq!(cache.policy.len(), 1);
+
+        // Making room collects the removed key before evicting anything.
+        cache.put("cd".to_string(), "1234567".to_string());
+        assert_eq!(cache.weight(), 10);
+        assert_eq!(cache.policy.len(), 1);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn test_replaced_entry_evicted_while_making_room() {
+        let mut cache: Cache<String, String, RandomState, Lengths> =
+            Cache::new(RandomState::new(), 10);
+        cache.put("abcd".to_string(), "x".to_string());
+        cache.put("c".to_string(), "x".to_string());
+        cache.get("c");
+        assert_eq!(cache.weight(), 9);
+
+        // Room for the new value evicts abcd itself, so its key and overhead need room too.
+        cache.put("abcd".to_string(), "xyz".to_string());
+        assert_eq!(cache.get("abcd"), Some(&"xyz".to_string()));
+        assert_eq!(cache.get("c"), None);
+        assert_eq!(cache.weight(), 8);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[derive(Debug, Clone)]
+    struct Overhead;
+    impl Weigher<u64, u64> for Overhead {
+        fn weigh_value(_value: &u64) -> usize {
+            0
+        }
+
+        fn entry_overhead(policy_slot_bytes: usize) -> usize {
+            entry_overhead_bytes::<u64, u64>(policy_slot_bytes)
+        }
+
+        fn expiration_overhead() -> usize {
+            expiration_overhead_bytes::<u64>()
+        }
+    }
+
+    /// The weight of one entry, and of one entry with an expiration deadline.
+    fn overhead<P: EvictionPolicy<u64> + Default>() -> (usize, usize) {
+        let mut cache: Cache<u64, u64, RandomState, Overhead, DefaultLifecycle, P> =
+            Cache::new(RandomState::new(), usize::MAX);
+        cache.put(1, 1);
+        let entry = cache.weight();
+        let soon = Instant::now() + std::time::Duration::from_millis(10);
+        cache.put_with_expiration(2, 2, soon);
+        let expiring = cache.weight() - entry;
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(
+            cache.weight(),
+            entry,
+            "the deadline is released with its entry"
+        );
+        (entry, expiring)
+    }
+
+    #[test]
+    fn test_overhead_follows_the_policy() {
+        let expiration = expiration_overhead_bytes::<u64>();
+        for (policy, (entry, expiring)) in [
+            (
+                Sieve::<u64>::default().slot_bytes(),
+                overhead::<Sieve<u64>>(),
+            ),
+            (
+                crate::Lru::<u64>::default().slot_bytes(),
+                overhead::<crate::Lru<u64>>(),
+            ),
+            (
+                crate::S3Fifo::<u64>::default().slot_bytes(),
+                overhead::<crate::S3Fifo<u64>>(),
+            ),
+        ] {
+            assert_eq!(entry, entry_overhead_bytes::<u64, u64>(policy));
+            assert_eq!(expiring, entry + expiration);
+        }
+        assert_ne!(
+            Sieve::<u64>::default().slot_bytes(),
+            crate::Lru::<u64>::default().slot_bytes()
+        );
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut cache: Cache<String, String, RandomState, Lengths> =
+            Cache::new(RandomState::new(), 10);
+        let later = Instant::now() + std::time::Duration::from_secs(60);
+        match cache.entry("ab".to_string()) {
+            Entry::Occupied(_) => panic!("ab is absent"),
+            Entry::Vacant(entry) => {
+                assert_eq!(
+                    entry.insert_with_expiration("xyz".to_string(), later),
+                    "xyz"
+                )
+            }
+        }
+        assert_eq!(cache.weight(), 6);
+
+        match cache.entry("ab".to_string()) {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.get(), "xyz");
+                assert_eq!(entry.insert("x".to_string()), "x");
+            }
+            Entry::Vacant(_) => panic!("ab is present"),
+        }
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.expiration("ab"), Some(later));
+
+        // Inserts through an entry make room like puts do.
+        assert_eq!(
+            cache.entry("cd".to_string()).or_insert("1234".to_string()),
+            "1234"
+        );
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.get("ab"), None);
+        assert_eq!(cache.weight(), 7);
+
+        match cache.entry("cd".to_string()) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), "1234"),
+            Entry::Vacant(_) => panic!("cd is present"),
+        }
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.stats().removals, 1);
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 3);
+    }
+
+    #[test]
+    fn test_removed_slot_does_not_evict_reinserted_key() {
+        let mut cache: Cache<String, String, RandomState> = Cache::new(RandomState::new(), 2);
+        cache.put("a".to_string(), "old".to_string());
+        cache.remove("a");
+        cache.put("a".to_string(), "new".to_string());
+        cache.put("b".to_string(), "value".to_string());
+        assert_eq!(cache.policy.len(), 3);
+
+        // The hand collects a's removed slot first. That must not touch the live a.
+        cache.put("c".to_string(), "value".to_string());
+        assert_eq!(cache.get("a"), Some(&"new".to_string()));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.policy.len(), 2);
+    }
+
+    #[test]
+    fn test_slots_stay_consistent() {
+        let mut cache: Cache<u64, u64, RandomState> = Cache::new(RandomState::new(), 64);
+        for _ in 0..100_000 {
+            let key = rand::random::<u64>() % 256;
+            match rand::random::<u8>() % 4 {
+                0 => {
+                    cache.remove(&key);
+                }
+                1 => {
+                    cache.get(&key);
+                }
+                _ => cache.put(key, key),
+            }
+        }
+        let mut live_slots = 0;
+        for slot in cache.policy.pool.iter_mut() {
+            if !slot.state.is_removed() {
+                live_slots += 1;
+            }
+        }
+        assert_eq!(live_slots, cache.map.len());
+        for (key, entry) in &cache.map {
+            assert_eq!(&cache.policy.pool[entry.slot].key, key);
+        }
+        assert!(cache.map.len() <= 64);
+    }
+
+    fn churn<P: EvictionPolicy<u64>>(policy: P) {
+        let mut cache: Cache<u64, u64, RandomState, One, DefaultLifecycle, P> =
+            Cache::new_with_policy(RandomState::new(), 64, DefaultLifecycle, policy);
+        for _ in 0..100_000 {
+            let key = rand::random::<u64>() % 256;
+            match rand::random::<u8>() % 4 {
+                0 => {
+                    cache.remove(&key);
+                }
+                1 => {
+                    cache.get(&key);
+                }
+                _ => cache.put(key, key),
+            }
+            assert!(cache.weight() <= 64);
+        }
+        assert!(cache.map.len() <= cache.policy.len());
+
+        // Every live key must still be evictable through its slot.
+        cache.set_max_weight(0);
+        cache.shrink(usize::MAX);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn test_every_policy_stays_consistent() {
+        churn(Sieve::default());
+        churn(crate::S3Fifo::default());
+        churn(crate::Lru::default());
+        churn(crate::AnyPolicy::default());
+    }
+
+    #[test]
+    fn test_sieve_evicts_unread_entries_first() {
+        let mut cache: Cache<&str, u64, RandomState> = Cache::new(RandomState::new(), 3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        cache.get("a");
+        cache.get("c");
+        // The hand starts at a, but b was never read, so it goes ahead of both.
+        cache.put("d", 4);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("c"), Some(&3));
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache: Cache<&str, u64, RandomState, One, DefaultLifecycle, crate::Lru<&str>> =
+            Cache::new(RandomState::new(), 3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        cache.get("a");
+        cache.put("d", 4);
+        assert_eq!(cache.get("b"), None);
+        cache.put("e", 5);
+        assert_eq!(cache.get("c"), None);
+        assert_eq!(cache.get("a"), Some(&1));
+    }
+
+    /// Reads 50 hot keys, then scans 1000 cold keys through a cache of 100.
+    fn hot_keys_after_scan<P: EvictionPolicy<u64> + Default>() -> usize {
+        let mut cache: Cache<u64, u64, RandomState, One, DefaultLifecycle, P> =
+            Cache::new(RandomState::new(), 100);
+        for key in 0..50 {
+            cache.put(key, key);
+            cache.get(&key);
+        }
+        for key in 1000..2000 {
+            cache.put(key, key);
+        }
+        (0..50).filter(|key| cache.contains_key(key)).count()
+    }
+
+    #[test]
+    fn test_s3_fifo_resists_scans() {
+        assert_eq!(hot_keys_after_scan::<crate::S3Fifo<u64>>(), 50);
+        assert_eq!(hot_keys_after_scan::<crate::Lru<u64>>(), 0);
     }
 }
@@ -0,0 +1,170 @@
+use std::{hash::BuildHasher, hash::Hash, time::Instant};
+
+use crate::{Cache, Cause, EvictionPolicy, Lifecycle, Sieve, Weigher};
+
+/// A view into one key of a Cache, from Cache::entry. The key was looked up once, as a get:
+/// a live entry is Occupied and counts as a hit, and anything else is Vacant and counts as a
+/// miss.
+///
+/// There is no mutable access to values, because the cache would not see a change in weight.
+/// Replace a value with insert instead; inserts make room like a put does.
+pub enum Entry<'a, K, V, S, W: Weigher<K, V>, L: Lifecycle<K, V>, P: EvictionPolicy<K> = Sieve<K>> {
+    Occupied(OccupiedEntry<'a, K, V, S, W, L, P>),
+    Vacant(VacantEntry<'a, K, V, S, W, L, P>),
+}
+
+pub struct OccupiedEntry<
+    'a,
+    K,
+    V,
+    S,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K> = Sieve<K>,
+> {
+    cache: &'a mut Cache<K, V, S, W, L, P>,
+    key: K,
+}
+
+pub struct VacantEntry<
+    'a,
+    K,
+    V,
+    S,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K> = Sieve<K>,
+> {
+    cache: &'a mut Cache<K, V, S, W, L, P>,
+    key: K,
+}
+
+impl<'a, K, V, S, W, L, P> Entry<'a, K, V, S, W, L, P>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
+    pub(crate) fn occupied(cache: &'a mut Cache<K, V, S, W, L, P>, key: K) -> Self {
+        Entry::Occupied(OccupiedEntry { cache, key })
+    }
+
+    pub(crate) fn vacant(cache: &'a mut Cache<K, V, S, W, L, P>, key: K) -> Self {
+        Entry::Vacant(VacantEntry { cache, key })
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, value: V) -> &'a V {
+        self.or_insert_with(|| value)
+    }
+
+    /// Insert the value from f if the entry is vacant. f only runs for vacant entries.
+    pub fn or_insert_with(self, f: impl FnOnce() -> V) -> &'a V {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+}
+
+impl<'a, K, V, S, W, L, P> OccupiedEntry<'a, K, V, S, W, L, P>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get(&self) -> &V {
+        self.cache
+            .peek_present(&self.key)
+            .expect("occupied entries are present")
+    }
+
+    pub fn into_ref(self) -> &'a V {
+        let cache: &'a Cache<K, V, S, W, L, P> = self.cache;
+        cache
+            .peek_present(&self.key)
+            .expect("occupied entries are present")
+    }
+
+    /// The entry's expiration deadline, if it has one.
+    pub fn expiration(&self) -> Option<Instant> {
+        self.cache.expiration(&self.key)
+    }
+
+    /// Replace the value. The entry keeps its expiration deadline.
+    pub fn insert(self, value: V) -> &'a V {
+        let expires_at = self.cache.expiration(&self.key);
+        self.cache
+            .insert(self.key.clone(), value, expires_at, Cause::Entry);
+        let cache: &'a Cache<K, V, S, W, L, P> = self.cache;
+        cache
+            .peek_present(&self.key)
+            .expect("inserted entries are present")
+    }
+
+    /// Replace the value and the expiration deadline.
+    pub fn insert_with_expiration(self, value: V, expires_at: Instant) -> &'a V {
+        self.cache
+            .insert(self.key.clone(), value, Some(expires_at), Cause::Entry);
+        let cache: &'a Cache<K, V, S, W, L, P> = self.cache;
+        cache
+            .peek_present(&self.key)
+            .expect("inserted entries are present")
+    }
+
+    pub fn remove(self) -> V {
+        self.cache
+            .remove_present(&self.key)
+            .expect("occupied entries are present")
+    }
+}
+
+impl<'a, K, V, S, W, L, P> VacantEntry<'a, K, V, S, W, L, P>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a V {
+        self.cache
+            .insert(self.key.clone(), value, None, Cause::Entry);
+        let cache: &'a Cache<K, V, S, W, L, P> = self.cache;
+        cache
+            .peek_present(&self.key)
+            .expect("inserted entries are present")
+    }
+
+    /// Insert an entry that is treated as absent once expires_at has passed.
+    pub fn insert_with_expiration(self, value: V, expires_at: Instant) -> &'a V {
+        self.cache
+            .insert(self.key.clone(), value, Some(expires_at), Cause::Entry);
+        let cache: &'a Cache<K, V, S, W, L, P> = self.cache;
+        cache
+            .peek_present(&self.key)
+            .expect("inserted entries are present")
+    }
+}
@@ -0,0 +1,32 @@
+use std::{cmp::Ordering, time::Instant};
+
+/// A deadline in the expiration queue. Ordered by deadline only, so keys do not need to be Ord.
+///
+/// The queue is not updated when an entry is replaced or removed, so a queued expiration can
+/// be stale. It is only acted upon if the live entry still has the same deadline.
+#[derive(Debug)]
+pub(crate) struct Expiration<K> {
+    pub(crate) expires_at: Instant,
+    pub(crate) key: K,
+}
+
+impl<K> PartialEq for Expiration<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.expires_at == other.expires_at
+    }
+}
+
+impl<K> Eq for Expiration<K> {}
+
+impl<K> PartialOrd for Expiration<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for Expiration<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the std max-heap pops the soonest deadline first.
+        other.expires_at.cmp(&self.expires_at)
+    }
+}
@@ -1,9 +1,32 @@
 mod cache;
+mod entry;
+mod expiration;
+mod loading;
+mod lru;
+mod policy;
+mod s3_fifo;
 mod segmented;
+mod sieve;
+mod state;
+mod stats;
 
+pub use cache::entry_overhead_bytes;
+pub use cache::expiration_overhead_bytes;
 pub use cache::Cache;
+pub use cache::Cause;
 pub use cache::DefaultLifecycle;
 pub use cache::Lifecycle;
 pub use cache::One;
 pub use cache::Weigher;
+pub use entry::Entry;
+pub use entry::OccupiedEntry;
+pub use entry::VacantEntry;
+pub use loading::LoadingCache;
+pub use lru::Lru;
+pub use policy::AnyPolicy;
+pub use policy::EvictionPolicy;
+pub use policy::Victim;
+pub use s3_fifo::S3Fifo;
 pub use segmented::SegmentedCache;
+pub use sieve::Sieve;
+pub use stats::Stats;
@@ -0,0 +1,241 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    future::Future,
+    hash::{BuildHasher, Hash},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+
+use crate::{
+    cache::DefaultLifecycle, EvictionPolicy, Lifecycle, One, SegmentedCache, Sieve, Weigher,
+};
+
+type Loader<K, V, E> = Box<dyn Fn(K) -> BoxFuture<'static, Result<V, E>> + Send + Sync>;
+
+/// A load in flight. Every waiter for the key polls the same load.
+type Load<V, E> = Shared<BoxFuture<'static, Result<V, Arc<E>>>>;
+
+/// A SegmentedCache that fills its misses with an async loader.
+///
+/// Concurrent misses for one key share a single load, so a hot key that expires or is evicted
+/// is loaded once rather than once per waiter. A failed load is not cached: its error goes to
+/// every waiter of that load, and the next get loads again.
+///
+/// A load keeps going as long as anyone waits for it. If every waiter gives up, the load is
+/// resumed by the next get for its key.
+pub struct LoadingCache<
+    K,
+    V,
+    E,
+    S: BuildHasher = std::hash::RandomState,
+    W: Weigher<K, V> = One,
+    L: Lifecycle<K, V> = DefaultLifecycle,
+    P: EvictionPolicy<K> = Sieve<K>,
+> {
+    cache: SegmentedCache<K, V, S, W, L, P>,
+    loader: Loader<K, V, E>,
+    in_flight: k_lock::Mutex<HashMap<K, Load<V, E>, S>>,
+    time_to_live: Option<Duration>,
+}
+
+impl<K, V, E, S, W, L, P> LoadingCache<K, V, E, S, W, L, P>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Default,
+    W: Weigher<K, V> + Clone,
+    L: Lifecycle<K, V> + Clone,
+    P: EvictionPolicy<K>,
+{
+    pub fn new<F, Fut>(cache: SegmentedCache<K, V, S, W, L, P>, loader: F) -> Self
+    where
+        F: Fn(K) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
+    {
+        Self {
+            cache,
+            loader: Box::new(move |key| loader(key).boxed()),
+            in_flight: k_lock::Mutex::new(HashMap::with_hasher(S::default())),
+            time_to_live: None,
+        }
+    }
+
+    /// Loaded values expire this long after they are loaded. Without it, they stay until they
+    /// are evicted or invalidated.
+    pub fn with_time_to_live(mut self, time_to_live: Duration) -> Self {
+        self.time_to_live = Some(time_to_live);
+        self
+    }
+
+    /// Get the value for key, loading it upon a miss. If the key is already loading, wait for
+    /// that load instead of starting another.
+    pub async fn get(&self, key: K) -> Result<V, Arc<E>>
+    where
+        E: Send + Sync + 'static,
+        V: Send + Sync + 'static,
+    {
+        let load = loop {
+            if let Some(value) = self.cache.get(&key) {
+                return Ok(value);
+            }
+            let mut in_flight = self.in_flight.lock().expect("mutex must not be poisoned");
+            // A load may have finished since the miss. Loads finish under this lock, so the
+            // value is either in the cache or still in flight.
+            if self.cache.contains_key(&key) {
+                continue;
+            }
+            break in_flight
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    (self.loader)(key.clone())
+                        .map(|result| result.map_err(Arc::new))
+                        .boxed()
+                        .shared()
+                })
+                .clone();
+        };
+
+        let result = load.clone().await;
+        self.finish(key, &load, &result);
+        result
+    }
+
+    /// Get the value for key if it is cached, without loading it.
+    pub fn get_if_present<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(key)
+    }
+
+    /// Remove the cached value for key, so the next get loads it again. A load that is already
+    /// in flight is not cancelled.
+    pub fn invalidate<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.remove(key)
+    }
+
+    /// The cache that holds the loaded values, for its statistics or for direct puts.
+    pub fn cache(&self) -> &SegmentedCache<K, V, S, W, L, P> {
+        &self.cache
+    }
+
+    /// The first waiter to see a load finish caches its value and retires it. Both happen
+    /// under the in flight lock, so a concurrent miss cannot start a second load in between.
+    fn finish(&self, key: K, load: &Load<V, E>, result: &Result<V, Arc<E>>) {
+        let mut in_flight = self.in_flight.lock().expect("mutex must not be poisoned");
+        if !in_flight
+            .get(&key)
+            .is_some_and(|current| current.ptr_eq(load))
+        {
+            return;
+        }
+        in_flight.remove(&key);
+        if let Ok(value) = result {
+            match self.time_to_live {
+                Some(time_to_live) => self.cache.put_with_expiration(
+                    key,
+                    value.clone(),
+                    Instant::now() + time_to_live,
+                ),
+                None => self.cache.put(key, value.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Loads key * 2 after a second, and counts its calls. Odd keys fail on the first call.
+    fn doubling_cache(calls: Arc<AtomicUsize>) -> LoadingCache<u64, u64, String> {
+        LoadingCache::new(SegmentedCache::new(4, 100), move |key| {
+            let call = calls.fetch_add(1, Ordering::Relaxed);
+            async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if key % 2 == 1 && call == 0 {
+                    Err(format!("failed to load {key}"))
+                } else {
+                    Ok(key * 2)
+                }
+            }
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_misses_share_a_load() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = doubling_cache(calls.clone());
+
+        let results = futures::future::join_all((0..10).map(|_| cache.get(2))).await;
+        assert!(results.into_iter().all(|result| result == Ok(4)));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        assert_eq!(cache.get(2).await, Ok(4));
+        assert_eq!(cache.get_if_present(&2), Some(4));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.cache().stats().inserts, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_errors_are_shared_but_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = doubling_cache(calls.clone());
+
+        let results = futures::future::join_all((0..5).map(|_| cache.get(3))).await;
+        let first = results[0].clone().expect_err("the first load fails");
+        assert_eq!(*first, "failed to load 3");
+        for result in results {
+            assert!(Arc::ptr_eq(
+                &result.expect_err("every waiter fails"),
+                &first
+            ));
+        }
+        assert_eq!(cache.get_if_present(&3), None);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        assert_eq!(cache.get(3).await, Ok(6));
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abandoned_loads_are_resumed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = doubling_cache(calls.clone());
+
+        let abandoned = tokio::time::timeout(Duration::from_millis(500), cache.get(4)).await;
+        assert!(abandoned.is_err());
+        assert_eq!(cache.get(4).await, Ok(8));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_and_invalidated_values_are_reloaded() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = doubling_cache(calls.clone()).with_time_to_live(Duration::ZERO);
+
+        assert_eq!(cache.get(2).await, Ok(4));
+        assert_eq!(cache.get_if_present(&2), None);
+        assert_eq!(cache.get(2).await, Ok(4));
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        let cache = doubling_cache(calls.clone());
+        assert_eq!(cache.get(2).await, Ok(4));
+        assert_eq!(cache.invalidate(&2), Some(4));
+        assert_eq!(cache.get(2).await, Ok(4));
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,137 @@
+use std::cell::Cell;
+
+use crate::{EvictionPolicy, Victim};
+
+/// No slot: the end of the list.
+const NIL: usize = usize::MAX;
+
+/// Least recently used: evicts the key that was read or written longest ago. It is a baseline
+/// to compare the other policies against.
+///
+/// Each read moves its key to the front of a list. Reads hold the cache by shared reference,
+/// so the list is linked through cells, and a Cache with this policy is not Sync. It is still
+/// Send, so it works in a SegmentedCache.
+#[derive(Debug)]
+pub struct Lru<K> {
+    nodes: Vec<Option<Node<K>>>,
+    /// Slots of nodes that were removed, to reuse.
+    free: Vec<usize>,
+    /// The most recently used slot.
+    head: Cell<usize>,
+    /// The least recently used slot.
+    tail: Cell<usize>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<K> {
+    key: K,
+    previous: Cell<usize>,
+    next: Cell<usize>,
+}
+
+impl<K> Default for Lru<K> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            head: Cell::new(NIL),
+            tail: Cell::new(NIL),
+            len: 0,
+        }
+    }
+}
+
+impl<K> Lru<K> {
+    fn node(&self, slot: usize) -> &Node<K> {
+        self.nodes[slot].as_ref().expect("linked slots have a node")
+    }
+
+    fn unlink(&self, slot: usize) {
+        let node = self.node(slot);
+        let (previous, next) = (node.previous.get(), node.next.get());
+        match previous {
+            NIL => self.head.set(next),
+            previous => self.node(previous).next.set(next),
+        }
+        match next {
+            NIL => self.tail.set(previous),
+            next => self.node(next).previous.set(previous),
+        }
+    }
+
+    fn push_front(&self, slot: usize) {
+        let node = self.node(slot);
+        let head = self.head.get();
+        node.previous.set(NIL);
+        node.next.set(head);
+        match head {
+            NIL => self.tail.set(slot),
+            head => self.node(head).previous.set(slot),
+        }
+        self.head.set(slot);
+    }
+
+    fn take(&mut self, slot: usize) -> K {
+        self.unlink(slot);
+        self.free.push(slot);
+        self.len -= 1;
+        self.nodes[slot]
+            .take()
+            .expect("linked slots have a node")
+            .key
+    }
+}
+
+impl<K> EvictionPolicy<K> for Lru<K> {
+    fn insert(&mut self, key: K) -> usize {
+        let node = Some(Node {
+            key,
+            previous: Cell::new(NIL),
+            next: Cell::new(NIL),
+        });
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.push_front(slot);
+        self.len += 1;
+        slot
+    }
+
+    fn visit(&self, slot: usize) {
+        if self.head.get() != slot {
+            self.unlink(slot);
+            self.push_front(slot);
+        }
+    }
+
+    fn remove(&mut self, slot: usize) -> Option<K> {
+        Some(self.take(slot))
+    }
+
+    fn evict(&mut self, _relocate: impl FnMut(&K, usize)) -> Option<Victim<K>> {
+        match self.tail.get() {
+            NIL => None,
+            tail => Some(Victim::Evict(self.take(tail))),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn slot_bytes(&self) -> usize {
+        size_of::<Option<Node<K>>>()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
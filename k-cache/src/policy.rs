@@ -0,0 +1,132 @@
+use std::hash::Hash;
+
+use crate::{Lru, S3Fifo, Sieve};
+
+/// Decides which entry a Cache evicts when it needs room.
+///
+/// The cache tells its policy about each key it inserts, reads and removes. The policy gives
+/// each key a slot: a number the cache keeps with the entry, to refer to the key cheaply.
+pub trait EvictionPolicy<K> {
+    /// Track a key that was inserted. Returns the key's slot.
+    fn insert(&mut self, key: K) -> usize;
+
+    /// The entry in slot was read. Reads only hold the cache by shared reference.
+    fn visit(&self, slot: usize);
+
+    /// The entry in slot got a new value.
+    fn update(&mut self, slot: usize) {
+        self.visit(slot)
+    }
+
+    /// The entry in slot was removed. Return its key to release it now, or None to keep it
+    /// until evict hands it back as a Victim::Collect. The cache charges for the key until then.
+    fn remove(&mut self, slot: usize) -> Option<K>;
+
+    /// Give up one slot, by evicting a live key or collecting a removed one. Call relocate
+    /// with each live key that moves to another slot, and its new slot. Returns None when no
+    /// keys are tracked.
+    fn evict(&mut self, relocate: impl FnMut(&K, usize)) -> Option<Victim<K>>;
+
+    /// The number of keys tracked, including removed keys that are not yet collected.
+    fn len(&self) -> usize;
+
+    /// An estimate of the bytes this policy spends on each key it tracks, besides the key's own
+    /// heap allocations. Weighers charge it through Weigher::entry_overhead.
+    fn slot_bytes(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget every key.
+    fn clear(&mut self);
+}
+
+/// The slot an eviction policy gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Victim<K> {
+    /// The live entry for this key must be evicted.
+    Evict(K),
+    /// This key's entry was already removed. Only the key is released.
+    Collect(K),
+}
+
+/// One of the built-in policies, chosen at runtime.
+#[derive(Debug)]
+pub enum AnyPolicy<K> {
+    Sieve(Sieve<K>),
+    S3Fifo(S3Fifo<K>),
+    Lru(Lru<K>),
+}
+
+impl<K> Default for AnyPolicy<K> {
+    fn default() -> Self {
+        AnyPolicy::Sieve(Sieve::default())
+    }
+}
+
+impl<K: Hash> EvictionPolicy<K> for AnyPolicy<K> {
+    fn insert(&mut self, key: K) -> usize {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.insert(key),
+            AnyPolicy::S3Fifo(policy) => policy.insert(key),
+            AnyPolicy::Lru(policy) => policy.insert(key),
+        }
+    }
+
+    fn visit(&self, slot: usize) {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.visit(slot),
+            AnyPolicy::S3Fifo(policy) => policy.visit(slot),
+            AnyPolicy::Lru(policy) => policy.visit(slot),
+        }
+    }
+
+    fn update(&mut self, slot: usize) {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.update(slot),
+            AnyPolicy::S3Fifo(policy) => policy.update(slot),
+            AnyPolicy::Lru(policy) => policy.update(slot),
+        }
+    }
+
+    fn remove(&mut self, slot: usize) -> Option<K> {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.remove(slot),
+            AnyPolicy::S3Fifo(policy) => policy.remove(slot),
+            AnyPolicy::Lru(policy) => policy.remove(slot),
+        }
+    }
+
+    fn evict(&mut self, relocate: impl FnMut(&K, usize)) -> Option<Victim<K>> {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.evict(relocate),
+            AnyPolicy::S3Fifo(policy) => policy.evict(relocate),
+            AnyPolicy::Lru(policy) => policy.evict(relocate),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.len(),
+            AnyPolicy::S3Fifo(policy) => policy.len(),
+            AnyPolicy::Lru(policy) => policy.len(),
+        }
+    }
+
+    fn slot_bytes(&self) -> usize {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.slot_bytes(),
+            AnyPolicy::S3Fifo(policy) => policy.slot_bytes(),
+            AnyPolicy::Lru(policy) => policy.slot_bytes(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            AnyPolicy::Sieve(policy) => policy.clear(),
+            AnyPolicy::S3Fifo(policy) => policy.clear(),
+            AnyPolicy::Lru(policy) => policy.clear(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{BuildHasher, Hash, RandomState},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{EvictionPolicy, Victim};
+
+/// Reads counted per key. More reads than this buy no more chances in the main queue.
+const MAX_FREQUENCY: u8 = 3;
+
+/// S3-FIFO, from "FIFO queues are all you need for cache eviction" (Yang et al., SOSP 2023).
+///
+/// New keys enter a small queue, about a tenth of the keys. Keys that are not read again
+/// before they reach its end are evicted, so a scan only churns the small queue. Keys that
+/// were read move to the main queue, which spares each key once per read it got, up to 3.
+/// A ghost queue remembers the hashes of keys evicted from the small queue; when one of them
+/// is inserted again, it goes straight to the main queue.
+///
+/// Removed keys stay queued until they reach the end of their queue.
+#[derive(Debug)]
+pub struct S3Fifo<K> {
+    nodes: Vec<Option<Node<K>>>,
+    /// Slots of nodes that were collected or evicted, to reuse.
+    free: Vec<usize>,
+    small: VecDeque<usize>,
+    main: VecDeque<usize>,
+    ghost: VecDeque<u64>,
+    /// How many times each hash appears in the ghost queue.
+    ghost_counts: HashMap<u64, u32>,
+    hasher: RandomState,
+}
+
+#[derive(Debug)]
+struct Node<K> {
+    key: K,
+    frequency: AtomicU8,
+    removed: bool,
+}
+
+impl<K> Default for S3Fifo<K> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            small: VecDeque::new(),
+            main: VecDeque::new(),
+            ghost: VecDeque::new(),
+            ghost_counts: HashMap::new(),
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl<K: Hash> S3Fifo<K> {
+    fn node_mut(&mut self, slot: usize) -> &mut Node<K> {
+        self.nodes[slot].as_mut().expect("queued slots have a node")
+    }
+
+    fn take(&mut self, slot: usize) -> K {
+        self.free.push(slot);
+        self.nodes[slot]
+            .take()
+            .expect("queued slots have a node")
+            .key
+    }
+
+    fn remember(&mut self, key: &K) {
+        let hash = self.hasher.hash_one(key);
+        self.ghost.push_back(hash);
+        *self.ghost_counts.entry(hash).or_default() += 1;
+        // Remember about as many evicted keys as the main queue holds.
+        while self.main.len().max(1) < self.ghost.len() {
+            let forgotten = self
+                .ghost
+                .pop_front()
+                .expect("the ghost queue is not empty");
+            if let Some(count) = self.ghost_counts.get_mut(&forgotten) {
+                *count -= 1;
+                if *count == 0 {
+                    self.ghost_counts.remove(&forgotten);
+                }
+            }
+        }
+    }
+
+    fn evict_small(&mut self) -> Option<Victim<K>> {
+        while let Some(slot) = self.small.pop_front() {
+            let node = self.node_mut(slot);
+            if node.removed {
+                return Some(Victim::Collect(self.take(slot)));
+            }
+            if 0 < *node.frequency.get_mut() {
+                *node.frequency.get_mut() = 0;
+                self.main.push_back(slot);
+                continue;
+            }
+            let key = self.take(slot);
+            self.remember(&key);
+            return Some(Victim::Evict(key));
+        }
+        None
+    }
+
+    fn evict_main(&mut self) -> Option<Victim<K>> {
+        while let Some(slot) = self.main.pop_front() {
+            let node = self.node_mut(slot);
+            if node.removed {
+                return Some(Victim::Collect(self.take(slot)));
+            }
+            let frequency = node.frequency.get_mut();
+            if 0 < *frequency {
+                *frequency -= 1;
+                self.main.push_back(slot);
+                continue;
+            }
+            return Some(Victim::Evict(self.take(slot)));
+        }
+        None
+    }
+}
+
+impl<K: Hash> EvictionPolicy<K> for S3Fifo<K> {
+    fn insert(&mut self, key: K) -> usize {
+        let returning = self.ghost_counts.contains_key(&self.hasher.hash_one(&key));
+        let node = Some(Node {
+            key,
+            frequency: AtomicU8::new(0),
+            removed: false,
+        });
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        if returning {
+            self.main.push_back(slot);
+        } else {
+            self.small.push_back(slot);
+        }
+        slot
+    }
+
+    fn visit(&self, slot: usize) {
+        let frequency = &self.nodes[slot]
+            .as_ref()
+            .expect("live slots have a node")
+            .frequency;
+        let current = frequency.load(Ordering::Relaxed);
+        if current < MAX_FREQUENCY {
+            frequency.store(current + 1, Ordering::Relaxed);
+        }
+    }
+
+    fn remove(&mut self, slot: usize) -> Option<K> {
+        self.node_mut(slot).removed = true;
+        None
+    }
+
+    fn evict(&mut self, _relocate: impl FnMut(&K, usize)) -> Option<Victim<K>> {
+        // Keep the small queue to about a tenth of the keys.
+        if self.len() <= 10 * self.small.len() || self.main.is_empty() {
+            self.evict_small().or_else(|| self.evict_main())
+        } else {
+            self.evict_main().or_else(|| self.evict_small())
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.small.len() + self.main.len()
+    }
+
+    fn slot_bytes(&self) -> usize {
+        // The node, and its place in the small or main queue. The ghost queue only holds keys
+        // that are no longer cached.
+        size_of::<Option<Node<K>>>() + size_of::<usize>()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -1,10 +1,13 @@
-use std::{borrow::Borrow, hash::BuildHasher};
+use std::{borrow::Borrow, hash::BuildHasher, sync::Arc, time::Instant};
 
 use crate::{
     cache::{DefaultLifecycle, Lifecycle},
-    Cache, One, Weigher,
+    stats::SharedStats,
+    Cache, Entry, EvictionPolicy, One, Sieve, Stats, Weigher,
 };
 
+type Segment<K, V, S, W, L, P> = k_lock::Mutex<Cache<K, V, S, W, L, P>>;
+
 #[derive(Debug)]
 pub struct SegmentedCache<
     K,
@@ -12,12 +15,31 @@ pub struct SegmentedCache<
     S: BuildHasher = std::hash::RandomState,
     W: Weigher<K, V> = One,
     L: Lifecycle<K, V> = DefaultLifecycle,
+    P: EvictionPolicy<K> = Sieve<K>,
 > {
-    segments: Vec<k_lock::Mutex<Cache<K, V, S, W, L>>>,
+    segments: Vec<Segment<K, V, S, W, L, P>>,
+    stats: Vec<Arc<SharedStats>>,
     hasher: S,
 }
 
-impl<K, V, S, W, L> SegmentedCache<K, V, S, W, L>
+impl<K, V, S, W, L, P> SegmentedCache<K, V, S, W, L, P>
+where
+    K: Eq + std::hash::Hash + Clone,
+    S: BuildHasher + Default,
+    W: Weigher<K, V>,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
+    fn from_segments(segments: Vec<Cache<K, V, S, W, L, P>>) -> Self {
+        Self {
+            stats: segments.iter().map(Cache::shared_stats).collect(),
+            segments: segments.into_iter().map(k_lock::Mutex::new).collect(),
+            hasher: S::default(),
+        }
+    }
+}
+
+impl<K, V, S, W, L> SegmentedCache<K, V, S, W, L, Sieve<K>>
 where
     K: Eq + std::hash::Hash + Clone,
     V: Clone,
@@ -25,75 +47,473 @@ where
     W: Weigher<K, V> + Clone,
     L: Lifecycle<K, V> + Default,
 {
+    /// A cache with the default Sieve policy. For other policies, see new_with_policy.
     pub fn new(segments: usize, max_weight: usize) -> Self {
         let weight_per_segment = max_weight / segments;
-        let segments = (0..segments)
-            .map(|_| {
-                k_lock::Mutex::new(Cache::new_with_lifecycle(
-                    S::default(),
-                    weight_per_segment,
-                    L::default(),
-                ))
-            })
-            .collect();
-        Self {
-            segments,
-            hasher: S::default(),
-        }
+        Self::from_segments(
+            (0..segments)
+                .map(|_| Cache::new(S::default(), weight_per_segment))
+                .collect(),
+        )
     }
 }
 
-impl<K, V, S, W, L> SegmentedCache<K, V, S, W, L>
+impl<K, V, S, W, L, P> SegmentedCache<K, V, S, W, L, P>
 where
     K: Eq + std::hash::Hash + Clone,
     V: Clone,
     S: BuildHasher + Default,
     W: Weigher<K, V> + Clone,
     L: Lifecycle<K, V> + Clone,
+    P: EvictionPolicy<K> + Default,
 {
     pub fn new_with_lifecycle(segments: usize, max_weight: usize, lifecycle: L) -> Self {
+        Self::new_with_policy(segments, max_weight, lifecycle, P::default)
+    }
+}
+
+impl<K, V, S, W, L, P> SegmentedCache<K, V, S, W, L, P>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Default,
+    W: Weigher<K, V> + Clone,
+    L: Lifecycle<K, V> + Clone,
+    P: EvictionPolicy<K>,
+{
+    /// Each segment evicts with its own policy, from policy.
+    pub fn new_with_policy(
+        segments: usize,
+        max_weight: usize,
+        lifecycle: L,
+        policy: impl Fn() -> P,
+    ) -> Self {
         let weight_per_segment = max_weight / segments;
-        let segments = (0..segments)
+        let segments: Vec<_> = (0..segments)
             .map(|_| {
-                k_lock::Mutex::new(Cache::new_with_lifecycle(
+                Cache::new_with_policy(
                     S::default(),
                     weight_per_segment,
                     lifecycle.clone(),
-                ))
+                    policy(),
+                )
             })
             .collect();
-        Self {
-            segments,
-            hasher: S::default(),
-        }
+        Self::from_segments(segments)
     }
+}
 
+impl<K, V, S, W, L, P> SegmentedCache<K, V, S, W, L, P>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Default,
+    W: Weigher<K, V> + Clone,
+    L: Lifecycle<K, V>,
+    P: EvictionPolicy<K>,
+{
     pub fn put(&self, key: K, value: V) {
-        let slot = self.hasher.hash_one(&key) as usize % self.segments.len();
-        self.segments[slot]
+        self.segments[self.segment_index(&key)]
             .lock()
             .expect("mutex must not be poisoned")
             .put(key, value)
     }
 
-    pub fn remove(&self, key: &K) -> Option<V> {
-        let slot = self.hasher.hash_one(key) as usize % self.segments.len();
-        self.segments[slot]
+    /// Put an entry that is treated as absent once expires_at has passed.
+    pub fn put_with_expiration(&self, key: K, value: V, expires_at: Instant) {
+        self.segments[self.segment_index(&key)]
+            .lock()
+            .expect("mutex must not be poisoned")
+            .put_with_expiration(key, value, expires_at)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.segments[self.segment_index(key)]
             .lock()
             .expect("mutex must not be poisoned")
             .remove(key)
     }
 
+    /// Remove the entry for key only if predicate returns true for its current value.
+    /// The predicate runs under the segment lock.
+    pub fn remove_if<Q>(&self, key: &Q, predicate: impl FnOnce(&V) -> bool) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.segments[self.segment_index(key)]
+            .lock()
+            .expect("mutex must not be poisoned")
+            .remove_if(key, predicate)
+    }
+
     pub fn get<Q>(&self, key: &Q) -> Option<V>
     where
         K: Borrow<Q>,
         Q: std::hash::Hash + Eq + ?Sized,
     {
-        let slot = self.hasher.hash_one(key) as usize % self.segments.len();
-        self.segments[slot]
+        self.segments[self.segment_index(key)]
             .lock()
             .expect("mutex must not be poisoned")
             .get(key)
             .cloned()
     }
+
+    /// Whether key has a live entry. Unlike get, it is not counted and does not visit the entry.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.segments[self.segment_index(key)]
+            .lock()
+            .expect("mutex must not be poisoned")
+            .contains_key(key)
+    }
+
+    /// Get the value for key, or insert the value from f if the key is absent. f runs under
+    /// the segment lock, so concurrent callers for one key only run it once. Keep it short:
+    /// it blocks every key in the segment.
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> V {
+        let index = self.segment_index(&key);
+        self.with_segment_at(index, |segment| {
+            segment.entry(key).or_insert_with(f).clone()
+        })
+    }
+
+    /// Replace the value for key with f's result for the current value, under the segment
+    /// lock. f gets None if the key is absent, and returning None removes the key. A replaced
+    /// entry keeps its expiration deadline. Returns the new value.
+    pub fn compute(&self, key: K, f: impl FnOnce(Option<&V>) -> Option<V>) -> Option<V> {
+        let index = self.segment_index(&key);
+        self.with_segment_at(index, |segment| match segment.entry(key) {
+            Entry::Occupied(entry) => match f(Some(entry.get())) {
+                Some(value) => Some(entry.insert(value).clone()),
+                None => {
+                    entry.remove();
+                    None
+                }
+            },
+            Entry::Vacant(entry) => f(None).map(|value| entry.insert(value).clone()),
+        })
+    }
+
+    /// Run f on the segment that owns key, holding the segment's lock. Use this to compose
+    /// several operations on one key atomically, like a read-modify-write.
+    pub fn with_segment<Q, R>(
+        &self,
+        key: &Q,
+        f: impl FnOnce(&mut Cache<K, V, S, W, L, P>) -> R,
+    ) -> R
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        f(&mut self.segments[self.segment_index(key)]
+            .lock()
+            .expect("mutex must not be poisoned"))
+    }
+
+    /// Run f on each segment in turn, holding that segment's lock. Each segment is seen in a
+    /// consistent state, but writes to other segments go on meanwhile.
+    pub fn with_each_segment(&self, mut f: impl FnMut(&mut Cache<K, V, S, W, L, P>)) {
+        for segment in &self.segments {
+            f(&mut segment.lock().expect("mutex must not be poisoned"));
+        }
+    }
+
+    /// The number of segments, for walking them one at a time with with_segment_at.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Run f on the segment at index, holding its lock. Unlike with_each_segment, the caller
+    /// can do other work, like awaiting, between segments.
+    pub fn with_segment_at<R>(
+        &self,
+        index: usize,
+        f: impl FnOnce(&mut Cache<K, V, S, W, L, P>) -> R,
+    ) -> R {
+        f(&mut self.segments[index]
+            .lock()
+            .expect("mutex must not be poisoned"))
+    }
+
+    /// Group items by the segment that owns their key, then run f on each item while holding
+    /// its segment's lock. Each segment is locked at most once.
+    pub fn with_segments<T, Q>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        key: impl Fn(&T) -> &Q,
+        mut f: impl FnMut(&mut Cache<K, V, S, W, L, P>, T),
+    ) where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let mut by_segment: Vec<Vec<T>> = (0..self.segments.len()).map(|_| Vec::new()).collect();
+        for item in items {
+            by_segment[self.segment_index(key(&item))].push(item);
+        }
+        for (segment, items) in self.segments.iter().zip(by_segment) {
+            if items.is_empty() {
+                continue;
+            }
+            let mut segment = segment.lock().expect("mutex must not be poisoned");
+            for item in items {
+                f(&mut segment, item);
+            }
+        }
+    }
+
+    /// Get several keys, locking each segment at most once. Results are in the order of keys.
+    pub fn get_many<Q>(&self, keys: &[&Q]) -> Vec<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let mut values = vec![None; keys.len()];
+        self.with_segments(
+            keys.iter().copied().enumerate(),
+            |(_, key)| *key,
+            |segment, (i, key)| values[i] = segment.get(key).cloned(),
+        );
+        values
+    }
+
+    /// Put several entries, locking each segment at most once.
+    pub fn put_many(&self, entries: impl IntoIterator<Item = (K, V)>) {
+        self.with_segments(
+            entries,
+            |(key, _)| key,
+            |segment, (key, value)| segment.put(key, value),
+        );
+    }
+
+    /// Remove every entry whose expiration deadline has passed, one segment at a time.
+    /// Returns the number removed.
+    pub fn purge_expired(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .lock()
+                    .expect("mutex must not be poisoned")
+                    .purge_expired()
+            })
+            .sum()
+    }
+
+    /// Change the max weight, split evenly across the segments like in new. Lowering it evicts
+    /// nothing by itself; see Cache::set_max_weight and shrink.
+    pub fn set_max_weight(&self, max_weight: usize) {
+        let weight_per_segment = max_weight / self.segments.len();
+        for segment in &self.segments {
+            segment
+                .lock()
+                .expect("mutex must not be poisoned")
+                .set_max_weight(weight_per_segment);
+        }
+    }
+
+    /// Evict up to max_evictions entries from each segment that is over its max weight, one
+    /// segment at a time. Returns the number removed; 0 once every segment fits.
+    pub fn shrink(&self, max_evictions: usize) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .lock()
+                    .expect("mutex must not be poisoned")
+                    .shrink(max_evictions)
+            })
+            .sum()
+    }
+
+    /// Copies of the live entries, one segment at a time. Each segment is copied under its lock
+    /// when the iterator reaches it, so entries written meanwhile may or may not be seen.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            segment
+                .lock()
+                .expect("mutex must not be poisoned")
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Keep only the live entries for which f returns true, one segment at a time. f runs
+    /// under the segment's lock.
+    pub fn retain(&self, mut f: impl FnMut(&K, &V) -> bool) {
+        for segment in &self.segments {
+            segment
+                .lock()
+                .expect("mutex must not be poisoned")
+                .retain(&mut f);
+        }
+    }
+
+    /// Remove every entry, one segment at a time.
+    pub fn clear(&self) {
+        for segment in &self.segments {
+            segment.lock().expect("mutex must not be poisoned").clear();
+        }
+    }
+
+    /// Statistics summed across the segments. Reading them does not take any segment's lock.
+    pub fn stats(&self) -> Stats {
+        self.stats.iter().map(|stats| stats.snapshot()).sum()
+    }
+
+    /// The index of the segment that owns key, for with_segment_at. Unlike with_segment, this
+    /// lets the caller move key into f after looking up its segment.
+    pub fn segment_index<Q>(&self, key: &Q) -> usize
+    where
+        Q: std::hash::Hash + ?Sized,
+    {
+        self.hasher.hash_one(key) as usize % self.segments.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_many() {
+        let cache: SegmentedCache<String, String> = SegmentedCache::new(4, 100);
+        cache.put_many((0..10).map(|i| (format!("key{i}"), format!("value{i}"))));
+        let keys: Vec<String> = (0..12).map(|i| format!("key{i}")).collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let values = cache.get_many(&keys);
+        for (i, value) in values.into_iter().enumerate() {
+            if i < 10 {
+                assert_eq!(value, Some(format!("value{i}")));
+            } else {
+                assert_eq!(value, None);
+            }
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 10);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.inserts, 10);
+        assert_eq!(stats.entries, 10);
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let cache: SegmentedCache<String, u64> = SegmentedCache::new(4, 100);
+        assert_eq!(cache.get_or_insert_with("a".to_string(), || 1), 1);
+        assert_eq!(
+            cache.get_or_insert_with("a".to_string(), || panic!("a is present")),
+            1
+        );
+
+        assert_eq!(
+            cache.compute("a".to_string(), |v| v.map(|v| v + 1)),
+            Some(2)
+        );
+        assert_eq!(cache.compute("b".to_string(), |v| v.map(|v| v + 1)), None);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.compute("b".to_string(), |_| Some(10)), Some(10));
+        assert_eq!(cache.compute("a".to_string(), |_| None), None);
+        assert_eq!(cache.get("a"), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats.inserts, 2);
+        assert_eq!(stats.updates, 1);
+        assert_eq!(stats.removals, 1);
+        assert_eq!(stats.entries, 1);
+    }
+
+    #[test]
+    fn test_concurrent_get_or_insert_with() {
+        let cache: SegmentedCache<u64, u64> = SegmentedCache::new(4, 100);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for key in 0..50 {
+                        let value = cache.get_or_insert_with(key, || {
+                            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            key * 2
+                        });
+                        assert_eq!(value, key * 2);
+                    }
+                });
+            }
+        });
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 50);
+    }
+
+    #[test]
+    fn test_iter_retain_and_clear() {
+        let cache: SegmentedCache<u64, u64> = SegmentedCache::new(4, 1000);
+        cache.put_many((0..100).map(|i| (i, i * 2)));
+        cache.retain(|key, _| key % 2 == 0);
+
+        let mut entries: Vec<_> = cache.iter().collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            (0..100)
+                .filter(|i| i % 2 == 0)
+                .map(|i| (i, i * 2))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(cache.stats().entries, 50);
+        assert_eq!(cache.stats().removals, 50);
+
+        let (mut segments, mut entries) = (0, 0);
+        cache.with_each_segment(|segment| {
+            segments += 1;
+            entries += segment.len();
+        });
+        assert_eq!((segments, entries), (4, 50));
+        let entries: usize = (0..cache.segment_count())
+            .map(|index| cache.with_segment_at(index, |segment| segment.len()))
+            .sum();
+        assert_eq!(entries, 50);
+
+        cache.clear();
+        assert_eq!(cache.iter().count(), 0);
+        assert_eq!(cache.stats().weight, 0);
+        assert_eq!(cache.stats().policy_length, 0);
+    }
+
+    #[test]
+    fn test_set_max_weight() {
+        let cache: SegmentedCache<u64, u64> = SegmentedCache::new(4, 400);
+        cache.put_many((0..1000).map(|i| (i, i)));
+        assert_eq!(cache.stats().max_weight, 400);
+        assert!(300 < cache.stats().weight);
+
+        cache.set_max_weight(100);
+        assert_eq!(cache.stats().max_weight, 100);
+        while 0 < cache.shrink(10) {}
+        assert!(cache.stats().weight <= 100);
+
+        cache.set_max_weight(800);
+        cache.put_many((1000..2000).map(|i| (i, i)));
+        assert!(600 < cache.stats().weight);
+        assert!(cache.stats().weight <= 800);
+    }
+
+    /// A lifecycle that is Default but not Clone, like before policies were pluggable.
+    #[derive(Default)]
+    struct Unclonable;
+    impl<K, V> Lifecycle<K, V> for Unclonable {}
+
+    #[test]
+    fn test_new_does_not_need_a_clonable_lifecycle() {
+        let cache: SegmentedCache<u64, u64, std::hash::RandomState, One, Unclonable> =
+            SegmentedCache::new(4, 100);
+        cache.put(1, 1);
+        assert_eq!(cache.get(&1), Some(1));
+    }
 }
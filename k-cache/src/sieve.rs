@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use crate::{
+    state::{EntryState, Sweep},
+    EvictionPolicy, Victim,
+};
+
+/// The default policy: a pool of keys and a hand that sweeps it. Keys read since the hand last
+/// passed them are spared once; the hand evicts the first key that was not.
+///
+/// Removed keys stay in the pool until the hand collects them.
+#[derive(Debug)]
+pub struct Sieve<K> {
+    pub(crate) pool: VecDeque<SieveEntry<K>>,
+    hand: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct SieveEntry<K> {
+    pub(crate) key: K,
+    pub(crate) state: EntryState,
+}
+
+impl<K> Default for Sieve<K> {
+    fn default() -> Self {
+        Self {
+            pool: VecDeque::new(),
+            hand: 0,
+        }
+    }
+}
+
+impl<K> EvictionPolicy<K> for Sieve<K> {
+    fn insert(&mut self, key: K) -> usize {
+        // A removed entry's slot may still hold this key, but it is marked removed, so the
+        // hand collects it without touching this new entry.
+        self.pool.push_back(SieveEntry {
+            key,
+            state: EntryState::fresh(),
+        });
+        self.pool.len() - 1
+    }
+
+    fn visit(&self, slot: usize) {
+        self.pool[slot].state.visit();
+    }
+
+    fn remove(&mut self, slot: usize) -> Option<K> {
+        self.pool[slot].state.remove();
+        None
+    }
+
+    fn evict(&mut self, mut relocate: impl FnMut(&K, usize)) -> Option<Victim<K>> {
+        loop {
+            if self.pool.is_empty() {
+                return None;
+            }
+            let hand = self.hand;
+            let sweep = self.pool[hand].state.sweep();
+            if sweep == Sweep::Spare {
+                self.hand = (hand + 1) % self.pool.len();
+                continue;
+            }
+
+            let sieve_entry = self
+                .pool
+                .swap_remove_back(hand)
+                .expect("the index must be present");
+            if let Some(moved) = self.pool.get_mut(hand) {
+                // The last slot moved under the hand. Removed slots have no map entry to update.
+                if !moved.state.is_removed() {
+                    relocate(&moved.key, hand);
+                }
+            }
+            if self.hand == self.pool.len() {
+                self.hand = 0;
+            }
+            return Some(match sweep {
+                Sweep::Evict => Victim::Evict(sieve_entry.key),
+                _ => Victim::Collect(sieve_entry.key),
+            });
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    fn slot_bytes(&self) -> usize {
+        size_of::<SieveEntry<K>>()
+    }
+
+    fn clear(&mut self) {
+        self.pool.clear();
+        self.hand = 0;
+    }
+}
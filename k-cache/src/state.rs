@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The state of a sieve pool slot, in one byte stored inline in the slot.
+///
+/// Gets only hold `&self`, so marking an entry visited goes through an atomic. Every other
+/// transition happens under `&mut`.
+#[derive(Debug)]
+pub(crate) struct EntryState(AtomicU8);
+
+/// Not read since the sieve hand last passed it. The next candidate for eviction.
+const COLD: u8 = 0;
+/// Read since the sieve hand last passed it.
+const VISITED: u8 = 1;
+/// Inserted, and not read since. Unlike a visited entry, the sieve hand does not spare it, so
+/// entries that were never read are evicted ahead of ones that were.
+const FRESH: u8 = 2;
+/// The entry was removed from the map. The slot only holds the key until the hand collects it.
+const REMOVED: u8 = 3;
+
+/// What the sieve hand should do with a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Sweep {
+    /// Spare the entry this time around.
+    Spare,
+    /// Evict the entry.
+    Evict,
+    /// Drop the slot. Its entry is already gone, so this is not an eviction.
+    Collect,
+}
+
+impl EntryState {
+    pub(crate) fn fresh() -> Self {
+        Self(AtomicU8::new(FRESH))
+    }
+
+    pub(crate) fn visit(&self) {
+        self.0.store(VISITED, Ordering::Relaxed);
+    }
+
+    pub(crate) fn remove(&mut self) {
+        *self.0.get_mut() = REMOVED;
+    }
+
+    pub(crate) fn is_removed(&mut self) -> bool {
+        *self.0.get_mut() == REMOVED
+    }
+
+    /// Decide the slot's fate as the sieve hand passes it. Spared entries go cold.
+    pub(crate) fn sweep(&mut self) -> Sweep {
+        let state = self.0.get_mut();
+        match *state {
+            COLD | FRESH => Sweep::Evict,
+            REMOVED => Sweep::Collect,
+            _ => {
+                *state = COLD;
+                Sweep::Spare
+            }
+        }
+    }
+}
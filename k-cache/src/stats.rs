@@ -0,0 +1,143 @@
+use std::{
+    iter::Sum,
+    ops::Add,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// A point-in-time copy of a cache's statistics.
+///
+/// Counters only grow. Each field is read separately, so a snapshot taken while the cache is
+/// in use may be slightly inconsistent across fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Gets that found a live entry.
+    pub hits: u64,
+    /// Gets that found no entry, or an expired one.
+    pub misses: u64,
+    /// Puts of keys that were absent, or whose entry had expired.
+    pub inserts: u64,
+    /// Puts that replaced a live entry.
+    pub updates: u64,
+    /// Entries removed explicitly.
+    pub removals: u64,
+    /// Entries evicted to make room for others.
+    pub evictions: u64,
+    /// Expired entries that were removed.
+    pub expirations: u64,
+    /// The total weight of the entries.
+    pub weight: usize,
+    /// The weight the cache holds itself to. The weight can exceed it for a while after the
+    /// max weight is lowered.
+    pub max_weight: usize,
+    /// The number of entries, including expired entries that are not yet removed.
+    pub entries: usize,
+    /// The number of keys the eviction policy tracks. With Sieve and S3-FIFO, removed entries
+    /// linger there until they are collected, so this can exceed the entry count.
+    pub policy_length: usize,
+}
+
+impl Add for Stats {
+    type Output = Stats;
+
+    fn add(self, other: Self) -> Self::Output {
+        Stats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            inserts: self.inserts + other.inserts,
+            updates: self.updates + other.updates,
+            removals: self.removals + other.removals,
+            evictions: self.evictions + other.evictions,
+            expirations: self.expirations + other.expirations,
+            weight: self.weight + other.weight,
+            max_weight: self.max_weight + other.max_weight,
+            entries: self.entries + other.entries,
+            policy_length: self.policy_length + other.policy_length,
+        }
+    }
+}
+
+impl Sum for Stats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Stats::default(), Add::add)
+    }
+}
+
+/// The live statistics of one cache. It is shared so it can be read without the cache's lock.
+#[derive(Debug, Default)]
+pub(crate) struct SharedStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    updates: AtomicU64,
+    removals: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+    weight: AtomicUsize,
+    max_weight: AtomicUsize,
+    entries: AtomicUsize,
+    policy_length: AtomicUsize,
+}
+
+impl SharedStats {
+    pub(crate) fn new(max_weight: usize) -> Self {
+        Self {
+            max_weight: AtomicUsize::new(max_weight),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn insert(&self) {
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn update(&self) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn removal(&self) {
+        self.removals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn expiration(&self) {
+        self.expirations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Publish the sizes of the cache after a change.
+    pub(crate) fn set_sizes(&self, weight: usize, entries: usize, policy_length: usize) {
+        self.weight.store(weight, Ordering::Relaxed);
+        self.entries.store(entries, Ordering::Relaxed);
+        self.policy_length.store(policy_length, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_max_weight(&self, max_weight: usize) {
+        self.max_weight.store(max_weight, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            inserts: self.inserts.load(Ordering::Relaxed),
+            updates: self.updates.load(Ordering::Relaxed),
+            removals: self.removals.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            weight: self.weight.load(Ordering::Relaxed),
+            max_weight: self.max_weight.load(Ordering::Relaxed),
+            entries: self.entries.load(Ordering::Relaxed),
+            policy_length: self.policy_length.load(Ordering::Relaxed),
+        }
+    }
+}
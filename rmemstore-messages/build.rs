@@ -13,6 +13,14 @@ fn main() {
         ".rmemstore.Get.blob",
         ".rmemstore.Put.key",
         ".rmemstore.Get.key",
+        ".rmemstore.Delete.key",
+        ".rmemstore.MultiGet.keys",
+        ".rmemstore.Increment.key",
+        ".rmemstore.GetField.key",
+        ".rmemstore.SetField.key",
+        ".rmemstore.DeleteField.key",
+        ".rmemstore.ReplicatedItem.key",
+        ".rmemstore.ReplicatedRemoval.key",
     ]);
     config.out_dir("./src");
 
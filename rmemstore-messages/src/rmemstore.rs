@@ -7,7 +7,7 @@ pub struct Rpc {
     pub id: u64,
     #[prost(uint32, tag = "2")]
     pub code: u32,
-    #[prost(oneof = "rpc::Command", tags = "3, 4")]
+    #[prost(oneof = "rpc::Command", tags = "3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14, 15")]
     pub command: ::core::option::Option<rpc::Command>,
 }
 /// Nested message and enum types in `Rpc`.
@@ -21,6 +21,36 @@ pub mod rpc {
         /// Response kind: Value
         #[prost(message, tag = "4")]
         Get(super::Get),
+        /// Response kind: ok
+        #[prost(message, tag = "5")]
+        Delete(super::Delete),
+        /// Response kind: compare_and_swap
+        #[prost(message, tag = "6")]
+        CompareAndSwap(super::CompareAndSwap),
+        /// Response kind: values
+        #[prost(message, tag = "7")]
+        MultiGet(super::MultiGet),
+        /// Response kind: versions
+        #[prost(message, tag = "8")]
+        MultiPut(super::MultiPut),
+        /// Response kind: integer
+        #[prost(message, tag = "9")]
+        Increment(super::Increment),
+        /// Response kind: value
+        #[prost(message, tag = "10")]
+        GetField(super::GetField),
+        /// Response kind: ok
+        #[prost(message, tag = "11")]
+        SetField(super::SetField),
+        /// Response kind: ok
+        #[prost(message, tag = "12")]
+        DeleteField(super::DeleteField),
+        /// Response kind: a stream of replication
+        #[prost(message, tag = "14")]
+        Replicate(super::Replicate),
+        /// Response kind: replication_status
+        #[prost(message, tag = "15")]
+        GetReplicationStatus(super::GetReplicationStatus),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -30,7 +60,7 @@ pub struct Response {
     pub id: u64,
     #[prost(uint32, tag = "2")]
     pub code: u32,
-    #[prost(oneof = "response::Kind", tags = "3, 4")]
+    #[prost(oneof = "response::Kind", tags = "3, 4, 5, 6, 7, 8, 9, 10, 11, 12")]
     pub kind: ::core::option::Option<response::Kind>,
 }
 /// Nested message and enum types in `Response`.
@@ -42,12 +72,38 @@ pub mod response {
         Ok(bool),
         #[prost(message, tag = "4")]
         Value(super::Value),
+        #[prost(message, tag = "5")]
+        VersionedValue(super::VersionedValue),
+        #[prost(message, tag = "6")]
+        CompareAndSwap(super::CompareAndSwapResult),
+        #[prost(message, tag = "7")]
+        Values(super::Values),
+        #[prost(message, tag = "8")]
+        Versions(super::Versions),
+        #[prost(sint64, tag = "9")]
+        Integer(i64),
+        /// The command failed. Any command can return an error instead of its usual kind.
+        #[prost(message, tag = "10")]
+        Error(super::Error),
+        #[prost(message, tag = "11")]
+        Replication(super::Replication),
+        #[prost(message, tag = "12")]
+        ReplicationStatus(super::ReplicationStatus),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Error {
+    #[prost(enumeration = "ErrorCode", tag = "1")]
+    pub code: i32,
+    /// A human readable description. It is not stable.
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Value {
-    #[prost(oneof = "value::Kind", tags = "1, 2, 3")]
+    #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4")]
     pub kind: ::core::option::Option<value::Kind>,
 }
 /// Nested message and enum types in `Value`.
@@ -61,6 +117,8 @@ pub mod value {
         String(::prost::alloc::string::String),
         #[prost(message, tag = "3")]
         Map(super::Map),
+        #[prost(sint64, tag = "4")]
+        Integer(i64),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -77,11 +135,382 @@ pub struct Put {
     pub key: ::prost::bytes::Bytes,
     #[prost(message, optional, tag = "2")]
     pub value: ::core::option::Option<Value>,
+    /// When unset, the value lives until it is deleted or evicted.
+    #[prost(oneof = "put::Expiry", tags = "3, 4")]
+    pub expiry: ::core::option::Option<put::Expiry>,
+}
+/// Nested message and enum types in `Put`.
+pub mod put {
+    /// When unset, the value lives until it is deleted or evicted.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
+    pub enum Expiry {
+        /// Time to live, relative to when the server receives the Put.
+        #[prost(uint64, tag = "3")]
+        TtlMillis(u64),
+        /// Absolute deadline, in milliseconds since the unix epoch.
+        #[prost(uint64, tag = "4")]
+        ExpiresAtUnixMillis(u64),
+    }
+}
+/// Every write gives the item a new version. Versions increase monotonically,
+/// and are never reused for a key, even after it is deleted.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VersionedValue {
+    #[prost(message, optional, tag = "1")]
+    pub value: ::core::option::Option<Value>,
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
 }
 /// Returns response.kind.value, or no value upon a miss.
+/// With versioned set, returns response.kind.versioned_value instead.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Get {
     #[prost(bytes = "bytes", tag = "1")]
     pub key: ::prost::bytes::Bytes,
+    #[prost(bool, tag = "2")]
+    pub versioned: bool,
+}
+/// Returns response.kind.ok, true if the key was present.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Delete {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+}
+/// Applies the put only if the key's current version is expected_version.
+/// An expected_version of 0 means the key must be absent.
+/// Returns response.kind.compare_and_swap
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompareAndSwap {
+    #[prost(message, optional, tag = "1")]
+    pub put: ::core::option::Option<Put>,
+    #[prost(uint64, tag = "2")]
+    pub expected_version: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CompareAndSwapResult {
+    #[prost(bool, tag = "1")]
+    pub swapped: bool,
+    /// When swapped, the new version. Otherwise the current version, or 0 if the key is absent.
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
+}
+/// Returns response.kind.values, with one entry per key in request order.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultiGet {
+    #[prost(bytes = "bytes", repeated, tag = "1")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::bytes::Bytes>,
+}
+/// Returns response.kind.versions, with one entry per put in request order.
+/// Every put is validated before any is applied. Puts to different keys are not atomic
+/// with respect to each other.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultiPut {
+    #[prost(message, repeated, tag = "1")]
+    pub puts: ::prost::alloc::vec::Vec<Put>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Values {
+    #[prost(message, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<OptionalValue>,
+}
+/// value is unset upon a miss.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OptionalValue {
+    #[prost(message, optional, tag = "1")]
+    pub value: ::core::option::Option<Value>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Versions {
+    #[prost(uint64, repeated, tag = "1")]
+    pub versions: ::prost::alloc::vec::Vec<u64>,
+}
+/// Atomically adds delta to an integer value. A missing key is created with the value delta.
+/// The key keeps its expiration deadline, if it has one.
+/// Returns response.kind.integer with the new value.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Increment {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(sint64, tag = "2")]
+    pub delta: i64,
+}
+/// Returns response.kind.value, or no value if the key or any field along the path is absent.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetField {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(string, repeated, tag = "2")]
+    pub path: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Sets the field at path, creating the key and any maps along the path that are absent.
+/// The key keeps its expiration deadline, if it has one.
+/// Returns response.kind.ok
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetField {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(string, repeated, tag = "2")]
+    pub path: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "3")]
+    pub value: ::core::option::Option<Value>,
+}
+/// Returns response.kind.ok, true if the field was present.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteField {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(string, repeated, tag = "2")]
+    pub path: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Replication: a replica follows a primary through one streaming rpc.
+/// The stream starts with a full state transfer: an item for each of the primary's live items,
+/// then transfer_complete. After that, it carries each write to the primary as it happens, in
+/// order, with a heartbeat several times a second. The stream ends if the replica falls too far
+/// behind, and the replica starts over with a new transfer.
+/// Evictions and expirations are not replicated: each server evicts and expires on its own.
+/// Returns a stream of response.kind.replication
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Replicate {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Replication {
+    #[prost(oneof = "replication::Kind", tags = "1, 2, 3, 4")]
+    pub kind: ::core::option::Option<replication::Kind>,
+}
+/// Nested message and enum types in `Replication`.
+pub mod replication {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Kind {
+        /// An item of the state transfer, or a later put.
+        #[prost(message, tag = "1")]
+        Item(super::ReplicatedItem),
+        /// A later delete.
+        #[prost(message, tag = "2")]
+        Removal(super::ReplicatedRemoval),
+        /// The state transfer is done. The value is the primary's next version.
+        #[prost(uint64, tag = "3")]
+        TransferComplete(u64),
+        #[prost(message, tag = "4")]
+        Heartbeat(super::Heartbeat),
+    }
+}
+/// Versions are the primary's, so a replica applies a write only if it is newer than its copy.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicatedItem {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(message, optional, tag = "2")]
+    pub value: ::core::option::Option<Value>,
+    #[prost(uint64, tag = "3")]
+    pub version: u64,
+    /// Absolute deadline, in milliseconds since the unix epoch, or 0 for none.
+    #[prost(uint64, tag = "4")]
+    pub expires_at_unix_millis: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicatedRemoval {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub key: ::prost::bytes::Bytes,
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
+}
+/// Every write before a heartbeat is sent before it.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Heartbeat {
+    /// The primary's wall clock when it sent the heartbeat, in milliseconds since the unix epoch.
+    #[prost(uint64, tag = "1")]
+    pub unix_millis: u64,
+}
+/// Returns response.kind.replication_status
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GetReplicationStatus {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ReplicationStatus {
+    #[prost(enumeration = "replication_status::Role", tag = "1")]
+    pub role: i32,
+    #[prost(enumeration = "replication_status::State", tag = "2")]
+    pub state: i32,
+    /// For a replica: how far behind the primary it may be, from the newest heartbeat it
+    /// applied. It keeps growing while the replica is disconnected, and includes any clock skew
+    /// between the servers.
+    #[prost(uint64, tag = "3")]
+    pub lag_millis: u64,
+    /// For a primary: the replicas following it.
+    #[prost(uint64, tag = "4")]
+    pub replicas: u64,
+}
+/// Nested message and enum types in `ReplicationStatus`.
+pub mod replication_status {
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum Role {
+        Unspecified = 0,
+        /// Accepts writes, and serves replication to replicas.
+        Primary = 1,
+        /// Follows a primary and rejects writes.
+        Replica = 2,
+    }
+    impl Role {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Role::Unspecified => "ROLE_UNSPECIFIED",
+                Role::Primary => "ROLE_PRIMARY",
+                Role::Replica => "ROLE_REPLICA",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "ROLE_UNSPECIFIED" => Some(Self::Unspecified),
+                "ROLE_PRIMARY" => Some(Self::Primary),
+                "ROLE_REPLICA" => Some(Self::Replica),
+                _ => None,
+            }
+        }
+    }
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum State {
+        Unspecified = 0,
+        /// A primary is always serving.
+        Serving = 1,
+        /// A replica that is not connected to its primary.
+        Disconnected = 2,
+        /// A replica receiving the state transfer. Its reads may miss.
+        Transferring = 3,
+        /// A replica following its primary's writes.
+        Following = 4,
+    }
+    impl State {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                State::Unspecified => "STATE_UNSPECIFIED",
+                State::Serving => "STATE_SERVING",
+                State::Disconnected => "STATE_DISCONNECTED",
+                State::Transferring => "STATE_TRANSFERRING",
+                State::Following => "STATE_FOLLOWING",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "STATE_UNSPECIFIED" => Some(Self::Unspecified),
+                "STATE_SERVING" => Some(Self::Serving),
+                "STATE_DISCONNECTED" => Some(Self::Disconnected),
+                "STATE_TRANSFERRING" => Some(Self::Transferring),
+                "STATE_FOLLOWING" => Some(Self::Following),
+                _ => None,
+            }
+        }
+    }
+}
+/// Codes are stable: match on the code, not on the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ErrorCode {
+    Unspecified = 0,
+    /// The Rpc had no command.
+    MissingCommand = 1,
+    /// A required value, or the put of a compare_and_swap, was not set.
+    MissingValue = 2,
+    /// A value could not be understood, like a value with no kind.
+    InvalidValue = 3,
+    /// Increment of a value that is not an integer.
+    NotAnInteger = 4,
+    /// Increment past the range of a signed 64 bit integer.
+    IntegerOverflow = 5,
+    /// A field command with an empty path.
+    EmptyFieldPath = 6,
+    /// A field path that passes through a value that is not a map.
+    NotAMap = 7,
+    /// A write, or a replicate, sent to a replica. Send it to the primary instead.
+    ReadOnlyReplica = 8,
+    /// A write the server could not append to its write log. It was not applied.
+    WriteNotLogged = 9,
+}
+impl ErrorCode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ErrorCode::Unspecified => "ERROR_CODE_UNSPECIFIED",
+            ErrorCode::MissingCommand => "ERROR_CODE_MISSING_COMMAND",
+            ErrorCode::MissingValue => "ERROR_CODE_MISSING_VALUE",
+            ErrorCode::InvalidValue => "ERROR_CODE_INVALID_VALUE",
+            ErrorCode::NotAnInteger => "ERROR_CODE_NOT_AN_INTEGER",
+            ErrorCode::IntegerOverflow => "ERROR_CODE_INTEGER_OVERFLOW",
+            ErrorCode::EmptyFieldPath => "ERROR_CODE_EMPTY_FIELD_PATH",
+            ErrorCode::NotAMap => "ERROR_CODE_NOT_A_MAP",
+            ErrorCode::ReadOnlyReplica => "ERROR_CODE_READ_ONLY_REPLICA",
+            ErrorCode::WriteNotLogged => "ERROR_CODE_WRITE_NOT_LOGGED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ERROR_CODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "ERROR_CODE_MISSING_COMMAND" => Some(Self::MissingCommand),
+            "ERROR_CODE_MISSING_VALUE" => Some(Self::MissingValue),
+            "ERROR_CODE_INVALID_VALUE" => Some(Self::InvalidValue),
+            "ERROR_CODE_NOT_AN_INTEGER" => Some(Self::NotAnInteger),
+            "ERROR_CODE_INTEGER_OVERFLOW" => Some(Self::IntegerOverflow),
+            "ERROR_CODE_EMPTY_FIELD_PATH" => Some(Self::EmptyFieldPath),
+            "ERROR_CODE_NOT_A_MAP" => Some(Self::NotAMap),
+            "ERROR_CODE_READ_ONLY_REPLICA" => Some(Self::ReadOnlyReplica),
+            "ERROR_CODE_WRITE_NOT_LOGGED" => Some(Self::WriteNotLogged),
+            _ => None,
+        }
+    }
 }
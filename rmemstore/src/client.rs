@@ -1,28 +1,72 @@
 use std::{
     net::SocketAddr,
-    sync::{atomic::AtomicU64, Arc},
+    sync::{atomic::AtomicU64, Arc, Mutex, Weak},
+    time::Duration,
 };
 
 use protosocket_prost::ProstSerializer;
-use protosocket_rpc::{client::Configuration, ProtosocketControlCode};
+use protosocket_rpc::{
+    client::{
+        Configuration, RpcClient, StreamConnector, TcpStreamConnector,
+        UnverifiedTlsStreamConnector, WebpkiTlsStreamConnector,
+    },
+    ProtosocketControlCode,
+};
 use rmemstore_messages::{response, Response, Rpc};
+use tokio::task::JoinHandle;
 
 use crate::{
-    types::{IntoKey, IntoValue, MemstoreValue},
+    tls::{self, RootCertificatesStreamConnector, TlsConfiguration},
+    types::{
+        CompareAndSwapOutcome, IntoKey, IntoValue, MemstoreValue, ReplicaState, ReplicationStatus,
+        VersionedValue,
+    },
     Error,
 };
 
 /// Cheap to clone, this is how you call rmemstored.
+///
+/// When the connection drops, the client reconnects in the background, waiting longer after
+/// each failed attempt. Commands in flight when it drops, and commands sent while it is
+/// reconnecting, fail with Error::ConnectionBroken rather than wait.
 #[derive(Debug, Clone)]
 pub struct Client {
-    client: protosocket_rpc::client::RpcClient<Rpc, Response>,
+    connection: Arc<Connection>,
     command_id: Arc<AtomicU64>,
 }
 
+/// The health of a client's connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// The connection dropped, and this many reconnection attempts have failed since.
+    Reconnecting {
+        attempts: u32,
+    },
+}
+
+/// Shared by a client's clones. The reconnection task only holds it weakly, so it stops once
+/// every clone is dropped.
+#[derive(Debug)]
+struct Connection {
+    address: SocketAddr,
+    state: Mutex<ConnectionStatus>,
+}
+
+#[derive(Debug)]
+struct ConnectionStatus {
+    /// None while reconnecting.
+    client: Option<RpcClient<Rpc, Response>>,
+    failed_attempts: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnectionConfiguration {
     max_message_size: usize,
     queued_messages: usize,
+    tls: Option<TlsConfiguration>,
+    min_reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
 }
 
 impl Default for ConnectionConfiguration {
@@ -30,6 +74,9 @@ impl Default for ConnectionConfiguration {
         Self {
             max_message_size: 4 * (2 << 20),
             queued_messages: 256,
+            tls: None,
+            min_reconnect_delay: Duration::from_millis(50),
+            max_reconnect_delay: Duration::from_secs(5),
         }
     }
 }
@@ -42,52 +89,148 @@ impl ConnectionConfiguration {
     pub fn queued_messages(&mut self, queued_messages: usize) {
         self.queued_messages = queued_messages;
     }
+
+    /// After the connection drops, the client waits min before its first reconnection
+    /// attempt, and twice as long after each failed attempt, up to max.
+    pub fn reconnect_delay(&mut self, min: Duration, max: Duration) {
+        self.min_reconnect_delay = min;
+        self.max_reconnect_delay = max.max(min);
+    }
+
+    /// Connect with tls, verifying the server as server_name against the public web pki roots.
+    pub fn tls(&mut self, server_name: &str) -> Result<(), crate::Error> {
+        self.tls = Some(TlsConfiguration::Webpki {
+            server_name: tls::parse_server_name(server_name)?,
+        });
+        Ok(())
+    }
+
+    /// Connect with tls, verifying the server as server_name against the root certificates
+    /// in a pem file. Use this for servers with certificates from a private authority.
+    pub fn tls_with_root_certificates(
+        &mut self,
+        server_name: &str,
+        root_certificates_pem: &[u8],
+    ) -> Result<(), crate::Error> {
+        self.tls = Some(TlsConfiguration::RootCertificates {
+            server_name: tls::parse_server_name(server_name)?,
+            roots: Arc::new(tls::parse_root_certificates(root_certificates_pem)?),
+        });
+        Ok(())
+    }
+
+    /// Connect with tls, but accept any server certificate. This encrypts traffic to a
+    /// self-signed server, but it does not authenticate the server.
+    pub fn tls_unverified(&mut self, server_name: &str) -> Result<(), crate::Error> {
+        self.tls = Some(TlsConfiguration::Unverified {
+            server_name: tls::parse_server_name(server_name)?,
+        });
+        Ok(())
+    }
 }
 
 impl Client {
+    /// Connect to rmemstored. Fails if the first connection fails; after that, the client
+    /// reconnects on its own.
     pub async fn connect(
         address: SocketAddr,
         configuration: ConnectionConfiguration,
     ) -> Result<Self, crate::Error> {
-        let mut client_configuration = Configuration::default();
-        client_configuration.max_buffer_length(configuration.max_message_size);
-        client_configuration.max_queued_outbound_messages(configuration.queued_messages);
-        let (client, connection_driver) = protosocket_rpc::client::connect::<
-            ProstSerializer<Response, Rpc>,
-            ProstSerializer<Response, Rpc>,
-        >(address, &client_configuration)
-        .await?;
-        tokio::spawn(connection_driver);
+        let (client, connection_driver) = open(address, &configuration).await?;
+        let connection = Arc::new(Connection {
+            address,
+            state: Mutex::new(ConnectionStatus {
+                client: Some(client),
+                failed_attempts: 0,
+            }),
+        });
+        tokio::spawn(reconnect(
+            Arc::downgrade(&connection),
+            configuration,
+            connection_driver,
+        ));
         Ok(Self {
-            client,
+            connection,
             command_id: Arc::new(AtomicU64::new(1)),
         })
     }
 
+    pub fn connection_state(&self) -> ConnectionState {
+        let state = self.connection.state();
+        match &state.client {
+            Some(client) if client.is_alive() => ConnectionState::Connected,
+            _ => ConnectionState::Reconnecting {
+                attempts: state.failed_attempts,
+            },
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection_state() == ConnectionState::Connected
+    }
+
     async fn send_command(
         &self,
         command: rmemstore_messages::rpc::Command,
     ) -> Result<rmemstore_messages::Response, crate::Error> {
+        let client = self
+            .connection
+            .state()
+            .client
+            .clone()
+            .ok_or(Error::ConnectionBroken("reconnecting"))?;
         let id = self
             .command_id
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        Ok(self
-            .client
+        let response = client
             .send_unary(rmemstore_messages::Rpc {
                 id,
                 code: ProtosocketControlCode::Normal.as_u8() as u32,
                 command: Some(command),
             })
-            .await?
-            .await?)
+            .await
+            .map_err(connection_error)?
+            .await
+            .map_err(connection_error)?;
+        match response.kind {
+            Some(response::Kind::Error(error)) => Err(error.into()),
+            _ => Ok(response),
+        }
     }
 
     pub async fn put(&self, key: impl IntoKey, value: impl IntoValue) -> Result<(), crate::Error> {
+        self.put_with_expiry(key, value, None).await
+    }
+
+    /// Put a value that the server stops serving once ttl has elapsed. The server counts in
+    /// milliseconds, so a ttl under a millisecond is rounded up to one.
+    pub async fn put_with_ttl(
+        &self,
+        key: impl IntoKey,
+        value: impl IntoValue,
+        ttl: Duration,
+    ) -> Result<(), crate::Error> {
+        let ttl_millis = u64::try_from(ttl.as_nanos().div_ceil(1_000_000)).unwrap_or(u64::MAX);
+        self.put_with_expiry(
+            key,
+            value,
+            Some(rmemstore_messages::put::Expiry::TtlMillis(ttl_millis)),
+        )
+        .await
+    }
+
+    async fn put_with_expiry(
+        &self,
+        key: impl IntoKey,
+        value: impl IntoValue,
+        expiry: Option<rmemstore_messages::put::Expiry>,
+    ) -> Result<(), crate::Error> {
         let command = rmemstore_messages::rpc::Command::Put(rmemstore_messages::Put {
             key: key.into_key(),
             value: Some(rmemstore_messages::Value {
                 kind: Some(value.into_value()),
             }),
+            expiry,
         });
         self.send_command(command).await?;
         Ok(())
@@ -96,6 +239,7 @@ impl Client {
     pub async fn get(&self, key: impl IntoKey) -> Result<Option<MemstoreValue>, crate::Error> {
         let command = rmemstore_messages::rpc::Command::Get(rmemstore_messages::Get {
             key: key.into_key(),
+            versioned: false,
         });
         let response = self.send_command(command).await?;
         match response.kind {
@@ -107,4 +251,370 @@ impl Client {
             _ => Ok(None),
         }
     }
+
+    /// Get a value along with its version, for use with compare_and_swap.
+    pub async fn get_versioned(
+        &self,
+        key: impl IntoKey,
+    ) -> Result<Option<VersionedValue>, crate::Error> {
+        let command = rmemstore_messages::rpc::Command::Get(rmemstore_messages::Get {
+            key: key.into_key(),
+            versioned: true,
+        });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::VersionedValue(rmemstore_messages::VersionedValue {
+                value: Some(value),
+                version,
+            })) => Ok(Some(VersionedValue {
+                value: value.try_into()?,
+                version,
+            })),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Put a value only if the key is still at expected_version. Use None to put only if
+    /// the key is absent.
+    pub async fn compare_and_swap(
+        &self,
+        key: impl IntoKey,
+        value: impl IntoValue,
+        expected_version: Option<u64>,
+    ) -> Result<CompareAndSwapOutcome, crate::Error> {
+        let command =
+            rmemstore_messages::rpc::Command::CompareAndSwap(rmemstore_messages::CompareAndSwap {
+                put: Some(rmemstore_messages::Put {
+                    key: key.into_key(),
+                    value: Some(rmemstore_messages::Value {
+                        kind: Some(value.into_value()),
+                    }),
+                    expiry: None,
+                }),
+                expected_version: expected_version.unwrap_or(0),
+            });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::CompareAndSwap(result)) => Ok(if result.swapped {
+                CompareAndSwapOutcome::Swapped {
+                    version: result.version,
+                }
+            } else {
+                CompareAndSwapOutcome::Conflict {
+                    current_version: (result.version != 0).then_some(result.version),
+                }
+            }),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Atomically add delta to an integer value, creating it at delta if it is absent.
+    /// Returns the new value.
+    pub async fn increment(&self, key: impl IntoKey, delta: i64) -> Result<i64, crate::Error> {
+        let command = rmemstore_messages::rpc::Command::Increment(rmemstore_messages::Increment {
+            key: key.into_key(),
+            delta,
+        });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Integer(value)) => Ok(value),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Get one field of a map value, addressed by a path of nested map keys.
+    pub async fn get_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Option<MemstoreValue>, crate::Error> {
+        let command = rmemstore_messages::rpc::Command::GetField(rmemstore_messages::GetField {
+            key: key.into_key(),
+            path: path.into_iter().map(Into::into).collect(),
+        });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Value(value)) => Ok(Some(value.try_into()?)),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Set one field of a map value without rewriting the rest of the map. The key and any
+    /// maps along the path are created if they are absent.
+    pub async fn set_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+        value: impl IntoValue,
+    ) -> Result<(), crate::Error> {
+        let command = rmemstore_messages::rpc::Command::SetField(rmemstore_messages::SetField {
+            key: key.into_key(),
+            path: path.into_iter().map(Into::into).collect(),
+            value: Some(rmemstore_messages::Value {
+                kind: Some(value.into_value()),
+            }),
+        });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Ok(_)) => Ok(()),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Remove one field of a map value. Returns true if the field was present.
+    pub async fn delete_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<bool, crate::Error> {
+        let command =
+            rmemstore_messages::rpc::Command::DeleteField(rmemstore_messages::DeleteField {
+                key: key.into_key(),
+                path: path.into_iter().map(Into::into).collect(),
+            });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Ok(removed)) => Ok(removed),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Remove a key. Returns true if the key was present.
+    pub async fn delete(&self, key: impl IntoKey) -> Result<bool, crate::Error> {
+        let command = rmemstore_messages::rpc::Command::Delete(rmemstore_messages::Delete {
+            key: key.into_key(),
+        });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Ok(existed)) => Ok(existed),
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Whether the server is a primary or a replica, and for a replica, how far behind it is.
+    pub async fn replication_status(&self) -> Result<ReplicationStatus, crate::Error> {
+        use rmemstore_messages::replication_status::{Role, State};
+
+        let command = rmemstore_messages::rpc::Command::GetReplicationStatus(
+            rmemstore_messages::GetReplicationStatus {},
+        );
+        let response = self.send_command(command).await?;
+        let status = match response.kind {
+            Some(response::Kind::ReplicationStatus(status)) => status,
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                return Err(Error::MalformedResponse("incorrect response type"));
+            }
+            None => return Err(Error::MalformedResponse("missing response kind")),
+        };
+        match status.role() {
+            Role::Primary => Ok(ReplicationStatus::Primary {
+                replicas: status.replicas,
+            }),
+            Role::Replica => Ok(ReplicationStatus::Replica {
+                state: match status.state() {
+                    State::Disconnected => ReplicaState::Disconnected,
+                    State::Transferring => ReplicaState::Transferring,
+                    State::Following => ReplicaState::Following,
+                    State::Unspecified | State::Serving => {
+                        return Err(Error::MalformedResponse("unknown replica state"))
+                    }
+                },
+                lag: Duration::from_millis(status.lag_millis),
+            }),
+            Role::Unspecified => Err(Error::MalformedResponse("unknown replication role")),
+        }
+    }
+
+    /// Get several keys in one round trip. Values are in the order of keys; misses are None.
+    pub async fn get_many<K: IntoKey>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<Vec<Option<MemstoreValue>>, crate::Error> {
+        let keys: Vec<_> = keys.into_iter().map(IntoKey::into_key).collect();
+        let expected = keys.len();
+        let command =
+            rmemstore_messages::rpc::Command::MultiGet(rmemstore_messages::MultiGet { keys });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Values(values)) => {
+                if values.values.len() != expected {
+                    return Err(Error::MalformedResponse("wrong number of values"));
+                }
+                values
+                    .values
+                    .into_iter()
+                    .map(|value| value.value.map(TryInto::try_into).transpose())
+                    .collect()
+            }
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+
+    /// Put several entries in one round trip. Returns the new versions in the order of entries.
+    pub async fn put_many<K: IntoKey, V: IntoValue>(
+        &self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Vec<u64>, crate::Error> {
+        let puts: Vec<_> = entries
+            .into_iter()
+            .map(|(key, value)| rmemstore_messages::Put {
+                key: key.into_key(),
+                value: Some(rmemstore_messages::Value {
+                    kind: Some(value.into_value()),
+                }),
+                expiry: None,
+            })
+            .collect();
+        let expected = puts.len();
+        let command =
+            rmemstore_messages::rpc::Command::MultiPut(rmemstore_messages::MultiPut { puts });
+        let response = self.send_command(command).await?;
+        match response.kind {
+            Some(response::Kind::Versions(versions)) => {
+                if versions.versions.len() != expected {
+                    return Err(Error::MalformedResponse("wrong number of versions"));
+                }
+                Ok(versions.versions)
+            }
+            Some(other) => {
+                log::debug!("unexpected response: {other:?}");
+                Err(Error::MalformedResponse("incorrect response type"))
+            }
+            None => Err(Error::MalformedResponse("missing response kind")),
+        }
+    }
+}
+
+impl Connection {
+    fn state(&self) -> std::sync::MutexGuard<'_, ConnectionStatus> {
+        self.state.lock().expect("lock must not be poisoned")
+    }
+}
+
+/// Commands fail with ConnectionBroken when the connection drops under them.
+fn connection_error(error: protosocket_rpc::Error) -> Error {
+    match error {
+        protosocket_rpc::Error::ConnectionIsClosed | protosocket_rpc::Error::CancelledRemotely => {
+            Error::ConnectionBroken("the connection dropped")
+        }
+        error => error.into(),
+    }
+}
+
+/// Wait for the connection to drop, then reconnect with backoff, for as long as the client
+/// is alive.
+async fn reconnect(
+    connection: Weak<Connection>,
+    configuration: ConnectionConfiguration,
+    mut connection_driver: JoinHandle<()>,
+) {
+    loop {
+        let _ = (&mut connection_driver).await;
+        let Some(address) = connection.upgrade().map(|connection| {
+            connection.state().client = None;
+            connection.address
+        }) else {
+            return;
+        };
+        log::warn!("connection to {address} dropped; reconnecting");
+
+        let mut delay = configuration.min_reconnect_delay;
+        connection_driver = loop {
+            tokio::time::sleep(delay).await;
+            let result = open(address, &configuration).await;
+            let Some(connection) = connection.upgrade() else {
+                return;
+            };
+            let mut state = connection.state();
+            match result {
+                Ok((client, connection_driver)) => {
+                    log::info!(
+                        "reconnected to {address} after {} failed attempts",
+                        state.failed_attempts
+                    );
+                    state.client = Some(client);
+                    state.failed_attempts = 0;
+                    break connection_driver;
+                }
+                Err(e) => {
+                    log::debug!("could not reconnect to {address}: {e}");
+                    state.failed_attempts += 1;
+                    delay = (delay * 2).min(configuration.max_reconnect_delay);
+                }
+            }
+        };
+    }
+}
+
+/// Open a connection, and spawn the task that drives it. The task ends when the connection
+/// drops.
+async fn open(
+    address: SocketAddr,
+    configuration: &ConnectionConfiguration,
+) -> Result<(RpcClient<Rpc, Response>, JoinHandle<()>), crate::Error> {
+    match configuration.tls.clone() {
+        None => open_with(address, configuration, TcpStreamConnector).await,
+        Some(TlsConfiguration::Webpki { server_name }) => {
+            let connector = WebpkiTlsStreamConnector::new(server_name);
+            open_with(address, configuration, connector).await
+        }
+        Some(TlsConfiguration::RootCertificates { server_name, roots }) => {
+            let connector = RootCertificatesStreamConnector::new(server_name, roots);
+            open_with(address, configuration, connector).await
+        }
+        Some(TlsConfiguration::Unverified { server_name }) => {
+            let connector = UnverifiedTlsStreamConnector::new(server_name);
+            open_with(address, configuration, connector).await
+        }
+    }
+}
+
+async fn open_with<TStreamConnector: StreamConnector>(
+    address: SocketAddr,
+    configuration: &ConnectionConfiguration,
+    stream_connector: TStreamConnector,
+) -> Result<(RpcClient<Rpc, Response>, JoinHandle<()>), crate::Error> {
+    let mut client_configuration = Configuration::new(stream_connector);
+    client_configuration.max_buffer_length(configuration.max_message_size);
+    client_configuration.max_queued_outbound_messages(configuration.queued_messages);
+    let (client, connection_driver) = protosocket_rpc::client::connect::<
+        ProstSerializer<Response, Rpc>,
+        ProstSerializer<Response, Rpc>,
+        TStreamConnector,
+    >(address, &client_configuration)
+    .await?;
+    Ok((client, tokio::spawn(connection_driver)))
 }
@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    ring::HashRing,
+    types::{CompareAndSwapOutcome, IntoKey, IntoValue, MemstoreValue, VersionedValue},
+    Client, ConnectionConfiguration, Error,
+};
+
+/// Points per node on the hash ring. More points spread keys more evenly, at the cost of
+/// memory and lookup time in the client.
+pub const DEFAULT_VIRTUAL_NODES: usize = 160;
+
+/// Cheap to clone, this is how you call a cluster of rmemstored nodes.
+///
+/// Keys are spread across the nodes by consistent hashing, so each key lives on one node.
+/// Adding or removing a node only moves the keys that it takes over or gives up; those keys
+/// read as misses until they are written again. Every client of a cluster must use the same
+/// nodes and virtual node count, to agree on where keys live.
+#[derive(Debug, Clone)]
+pub struct ClusterClient {
+    configuration: ConnectionConfiguration,
+    cluster: Arc<RwLock<Cluster>>,
+}
+
+/// Items for one node, each with its index in the caller's batch.
+type Batch<T> = Vec<(usize, T)>;
+
+#[derive(Debug)]
+struct Cluster {
+    ring: HashRing,
+    clients: HashMap<SocketAddr, Client>,
+}
+
+impl ClusterClient {
+    /// Connect to each node. Fails if any node cannot be reached.
+    pub async fn connect(
+        nodes: impl IntoIterator<Item = SocketAddr>,
+        configuration: ConnectionConfiguration,
+    ) -> Result<Self, Error> {
+        Self::connect_with_virtual_nodes(nodes, configuration, DEFAULT_VIRTUAL_NODES).await
+    }
+
+    pub async fn connect_with_virtual_nodes(
+        nodes: impl IntoIterator<Item = SocketAddr>,
+        configuration: ConnectionConfiguration,
+        virtual_nodes: usize,
+    ) -> Result<Self, Error> {
+        let cluster = Self {
+            configuration,
+            cluster: Arc::new(RwLock::new(Cluster {
+                ring: HashRing::new(virtual_nodes),
+                clients: HashMap::new(),
+            })),
+        };
+        for node in nodes {
+            cluster.add_node(node).await?;
+        }
+        Ok(cluster)
+    }
+
+    /// Connect to a node and start routing its share of keys to it. Returns false if the node
+    /// was already in the cluster.
+    pub async fn add_node(&self, node: SocketAddr) -> Result<bool, Error> {
+        if self.cluster().clients.contains_key(&node) {
+            return Ok(false);
+        }
+        // Connect before taking the lock, so routing goes on meanwhile.
+        let client = Client::connect(node, self.configuration.clone()).await?;
+        let mut cluster = self.cluster.write().expect("lock must not be poisoned");
+        if cluster.clients.contains_key(&node) {
+            return Ok(false);
+        }
+        cluster.ring.add(node);
+        cluster.clients.insert(node, client);
+        Ok(true)
+    }
+
+    /// Stop routing keys to a node, and hand its keys to the others. Returns false if the node
+    /// was not in the cluster.
+    pub fn remove_node(&self, node: SocketAddr) -> bool {
+        let mut cluster = self.cluster.write().expect("lock must not be poisoned");
+        cluster.ring.remove(node);
+        cluster.clients.remove(&node).is_some()
+    }
+
+    /// Each node once, sorted.
+    pub fn nodes(&self) -> Vec<SocketAddr> {
+        self.cluster().ring.nodes()
+    }
+
+    /// The node that key lives on, or None when the cluster has no nodes.
+    pub fn node_for(&self, key: impl IntoKey) -> Option<SocketAddr> {
+        self.cluster().ring.node_for(&key.into_key())
+    }
+
+    pub async fn put(&self, key: impl IntoKey, value: impl IntoValue) -> Result<(), Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.put(key, value).await
+    }
+
+    /// Put a value that the server stops serving once ttl has elapsed.
+    pub async fn put_with_ttl(
+        &self,
+        key: impl IntoKey,
+        value: impl IntoValue,
+        ttl: Duration,
+    ) -> Result<(), Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.put_with_ttl(key, value, ttl).await
+    }
+
+    pub async fn get(&self, key: impl IntoKey) -> Result<Option<MemstoreValue>, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.get(key).await
+    }
+
+    /// Get a value along with its version, for use with compare_and_swap.
+    pub async fn get_versioned(&self, key: impl IntoKey) -> Result<Option<VersionedValue>, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.get_versioned(key).await
+    }
+
+    /// Put a value only if the key is still at expected_version. Use None to put only if
+    /// the key is absent.
+    pub async fn compare_and_swap(
+        &self,
+        key: impl IntoKey,
+        value: impl IntoValue,
+        expected_version: Option<u64>,
+    ) -> Result<CompareAndSwapOutcome, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?
+            .compare_and_swap(key, value, expected_version)
+            .await
+    }
+
+    /// Atomically add delta to an integer value, creating it at delta if it is absent.
+    /// Returns the new value.
+    pub async fn increment(&self, key: impl IntoKey, delta: i64) -> Result<i64, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.increment(key, delta).await
+    }
+
+    /// Get one field of a map value, addressed by a path of nested map keys.
+    pub async fn get_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Option<MemstoreValue>, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.get_field(key, path).await
+    }
+
+    /// Set one field of a map value without rewriting the rest of the map.
+    pub async fn set_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+        value: impl IntoValue,
+    ) -> Result<(), Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.set_field(key, path, value).await
+    }
+
+    /// Remove one field of a map value. Returns true if the field was present.
+    pub async fn delete_field(
+        &self,
+        key: impl IntoKey,
+        path: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<bool, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.delete_field(key, path).await
+    }
+
+    /// Remove a key. Returns true if the key was present.
+    pub async fn delete(&self, key: impl IntoKey) -> Result<bool, Error> {
+        let key = key.into_key();
+        self.client_for(&key)?.delete(key).await
+    }
+
+    /// Get several keys, with one request to each node that owns some of them, sent
+    /// concurrently. Values are in the order of keys; misses are None.
+    pub async fn get_many<K: IntoKey>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<Vec<Option<MemstoreValue>>, Error> {
+        let keys: Vec<_> = keys.into_iter().map(IntoKey::into_key).collect();
+        let mut values = vec![None; keys.len()];
+        let batches = self.split(keys, |key| key)?;
+        let results =
+            futures::future::try_join_all(batches.into_iter().map(|(client, batch)| async move {
+                let (indices, keys): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+                let values = client.get_many(keys).await?;
+                Ok::<_, Error>(indices.into_iter().zip(values))
+            }))
+            .await?;
+        for (index, value) in results.into_iter().flatten() {
+            values[index] = value;
+        }
+        Ok(values)
+    }
+
+    /// Put several entries, with one request to each node that owns some of them, sent
+    /// concurrently. Returns the new versions in the order of entries. Upon an error, the
+    /// puts to other nodes may still have been applied.
+    pub async fn put_many<K: IntoKey, V: IntoValue>(
+        &self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Vec<u64>, Error> {
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|(key, value)| (key.into_key(), value.into_value()))
+            .collect();
+        let mut versions = vec![0; entries.len()];
+        let batches = self.split(entries, |(key, _)| key)?;
+        let results =
+            futures::future::try_join_all(batches.into_iter().map(|(client, batch)| async move {
+                let (indices, entries): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+                let versions = client.put_many(entries).await?;
+                Ok::<_, Error>(indices.into_iter().zip(versions))
+            }))
+            .await?;
+        for (index, version) in results.into_iter().flatten() {
+            versions[index] = version;
+        }
+        Ok(versions)
+    }
+
+    fn cluster(&self) -> std::sync::RwLockReadGuard<'_, Cluster> {
+        self.cluster.read().expect("lock must not be poisoned")
+    }
+
+    fn client_for(&self, key: &[u8]) -> Result<Client, Error> {
+        let cluster = self.cluster();
+        let node = cluster.ring.node_for(key).ok_or(Error::NoNodes)?;
+        Ok(cluster.clients[&node].clone())
+    }
+
+    /// Group items by the node that owns their key, keeping each item's index.
+    fn split<T>(
+        &self,
+        items: Vec<T>,
+        key: impl Fn(&T) -> &Bytes,
+    ) -> Result<Vec<(Client, Batch<T>)>, Error> {
+        let cluster = self.cluster();
+        let mut batches: HashMap<SocketAddr, Batch<T>> = HashMap::new();
+        for (index, item) in items.into_iter().enumerate() {
+            let node = cluster.ring.node_for(key(&item)).ok_or(Error::NoNodes)?;
+            batches.entry(node).or_default().push((index, item));
+        }
+        Ok(batches
+            .into_iter()
+            .map(|(node, batch)| (cluster.clients[&node].clone(), batch))
+            .collect())
+    }
+}
@@ -1,3 +1,5 @@
+use rmemstore_messages::ErrorCode;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("socket error: {0}")]
@@ -6,4 +8,50 @@ pub enum Error {
     ConnectionBroken(&'static str),
     #[error("malformed response: {0}")]
     MalformedResponse(&'static str),
+    #[error("tls configuration: {0}")]
+    TlsConfiguration(String),
+    /// The server rejected the request as malformed, like a put with no value.
+    #[error("invalid request ({code:?}): {message}")]
+    InvalidRequest { code: ErrorCode, message: String },
+    /// The stored value is the wrong type for the command, like incrementing a string.
+    #[error("wrong value type ({code:?}): {message}")]
+    WrongType { code: ErrorCode, message: String },
+    #[error("integer overflow: {0}")]
+    IntegerOverflow(String),
+    /// The server is a replica, which rejects writes. Write to its primary instead.
+    #[error("read only replica: {0}")]
+    ReadOnlyReplica(String),
+    /// The server could not append the write to its write log, so it did not apply it.
+    #[error("write not logged: {0}")]
+    WriteNotLogged(String),
+    /// A cluster client with no nodes to route to.
+    #[error("the cluster has no nodes")]
+    NoNodes,
+    /// An error code this client does not recognize, possibly from a newer server.
+    #[error("server error ({code}): {message}")]
+    ServerError { code: i32, message: String },
+}
+
+impl From<rmemstore_messages::Error> for Error {
+    fn from(error: rmemstore_messages::Error) -> Self {
+        let message = error.message;
+        match ErrorCode::try_from(error.code) {
+            Ok(
+                code @ (ErrorCode::MissingCommand
+                | ErrorCode::MissingValue
+                | ErrorCode::InvalidValue
+                | ErrorCode::EmptyFieldPath),
+            ) => Error::InvalidRequest { code, message },
+            Ok(code @ (ErrorCode::NotAnInteger | ErrorCode::NotAMap)) => {
+                Error::WrongType { code, message }
+            }
+            Ok(ErrorCode::IntegerOverflow) => Error::IntegerOverflow(message),
+            Ok(ErrorCode::ReadOnlyReplica) => Error::ReadOnlyReplica(message),
+            Ok(ErrorCode::WriteNotLogged) => Error::WriteNotLogged(message),
+            Ok(ErrorCode::Unspecified) | Err(_) => Error::ServerError {
+                code: error.code,
+                message,
+            },
+        }
+    }
 }
@@ -1,7 +1,15 @@
 mod client;
+mod cluster;
 mod error;
+mod ring;
+mod tls;
 pub mod types;
 
 pub use client::Client;
 pub use client::ConnectionConfiguration;
+pub use client::ConnectionState;
+pub use cluster::ClusterClient;
+pub use cluster::DEFAULT_VIRTUAL_NODES;
 pub use error::Error;
+pub use rmemstore_messages::ErrorCode;
+pub use tls::RootCertificatesStreamConnector;
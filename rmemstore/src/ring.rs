@@ -0,0 +1,191 @@
+use std::net::SocketAddr;
+
+/// A consistent hashing ring. Each node owns virtual_nodes points on the ring, and a key
+/// belongs to the node of the first point at or after the key's hash. Adding or removing a
+/// node only moves the keys between its points and the points before them.
+///
+/// Hashes are fixed, so every client agrees on where a key lives, across processes and
+/// versions.
+#[derive(Debug, Clone)]
+pub(crate) struct HashRing {
+    virtual_nodes: usize,
+    /// Sorted by hash, then by node, so collisions resolve the same way everywhere.
+    points: Vec<(u64, SocketAddr)>,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: usize) -> Self {
+        Self {
+            virtual_nodes: virtual_nodes.max(1),
+            points: Vec::new(),
+        }
+    }
+
+    /// Returns false if node was already on the ring.
+    pub fn add(&mut self, node: SocketAddr) -> bool {
+        if self.contains(node) {
+            return false;
+        }
+        let name = node.to_string();
+        for replica in 0..self.virtual_nodes {
+            let point = hash(format!("{name}#{replica}").as_bytes());
+            let index = self.points.partition_point(|other| *other < (point, node));
+            self.points.insert(index, (point, node));
+        }
+        true
+    }
+
+    /// Returns false if node was not on the ring.
+    pub fn remove(&mut self, node: SocketAddr) -> bool {
+        let before = self.points.len();
+        self.points.retain(|(_, other)| *other != node);
+        self.points.len() != before
+    }
+
+    pub fn contains(&self, node: SocketAddr) -> bool {
+        self.points.iter().any(|(_, other)| *other == node)
+    }
+
+    /// The node that owns key, or None when the ring is empty.
+    pub fn node_for(&self, key: &[u8]) -> Option<SocketAddr> {
+        let first = self.points.first()?;
+        let point = hash(key);
+        let index = self.points.partition_point(|(other, _)| *other < point);
+        // Past the last point, the ring wraps around to the first.
+        Some(self.points.get(index).unwrap_or(first).1)
+    }
+
+    /// Each node once, sorted.
+    pub fn nodes(&self) -> Vec<SocketAddr> {
+        let mut nodes: Vec<_> = self.points.iter().map(|(_, node)| *node).collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+}
+
+/// FNV-1a, with a final mix so that similar inputs, like a node's virtual node names, spread
+/// across the whole ring.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, net::SocketAddr};
+
+    use super::HashRing;
+
+    fn node(i: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], 9466 + i))
+    }
+
+    fn keys() -> Vec<Vec<u8>> {
+        (0..20_000)
+            .map(|i| format!("key {i}").into_bytes())
+            .collect()
+    }
+
+    fn owners(ring: &HashRing, keys: &[Vec<u8>]) -> Vec<SocketAddr> {
+        keys.iter()
+            .map(|key| ring.node_for(key).expect("ring has nodes"))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_ring_has_no_owner() {
+        let mut ring = HashRing::new(16);
+        assert_eq!(ring.node_for(b"key"), None);
+        ring.add(node(0));
+        assert_eq!(ring.node_for(b"key"), Some(node(0)));
+        ring.remove(node(0));
+        assert_eq!(ring.node_for(b"key"), None);
+    }
+
+    #[test]
+    fn test_keys_spread_evenly() {
+        let mut ring = HashRing::new(160);
+        for i in 0..8 {
+            ring.add(node(i));
+        }
+        let keys = keys();
+        let mut counts: HashMap<SocketAddr, usize> = HashMap::new();
+        for owner in owners(&ring, &keys) {
+            *counts.entry(owner).or_default() += 1;
+        }
+        let even = keys.len() / 8;
+        for (node, count) in counts {
+            assert!(
+                even * 3 / 4 < count && count < even * 5 / 4,
+                "{node} owns {count} keys, expected about {even}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_adding_a_node_only_moves_keys_to_it() {
+        let mut ring = HashRing::new(160);
+        for i in 0..8 {
+            ring.add(node(i));
+        }
+        let keys = keys();
+        let before = owners(&ring, &keys);
+        assert!(ring.add(node(8)));
+        assert!(!ring.add(node(8)), "adding twice is a no-op");
+        let after = owners(&ring, &keys);
+
+        let moved = before
+            .iter()
+            .zip(&after)
+            .filter(|(before, after)| before != after)
+            .inspect(|(_, after)| assert_eq!(**after, node(8)))
+            .count();
+        let share = keys.len() / 9;
+        assert!(
+            share / 2 < moved && moved < share * 3 / 2,
+            "{moved} keys moved, expected about {share}"
+        );
+    }
+
+    #[test]
+    fn test_removing_a_node_only_moves_its_keys() {
+        let mut ring = HashRing::new(160);
+        for i in 0..8 {
+            ring.add(node(i));
+        }
+        let keys = keys();
+        let before = owners(&ring, &keys);
+        assert!(ring.remove(node(3)));
+        assert!(!ring.remove(node(3)), "removing twice is a no-op");
+        let after = owners(&ring, &keys);
+
+        for (before, after) in before.iter().zip(&after) {
+            if *before == node(3) {
+                assert_ne!(*after, node(3));
+            } else {
+                assert_eq!(before, after);
+            }
+        }
+        assert_eq!(ring.nodes().len(), 7);
+    }
+
+    #[test]
+    fn test_placement_is_independent_of_insertion_order() {
+        let (mut forward, mut backward) = (HashRing::new(40), HashRing::new(40));
+        for i in 0..5 {
+            forward.add(node(i));
+            backward.add(node(4 - i));
+        }
+        let keys = keys();
+        assert_eq!(owners(&forward, &keys), owners(&backward, &keys));
+    }
+}
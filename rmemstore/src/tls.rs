@@ -0,0 +1,92 @@
+use std::{future::Future, sync::Arc};
+
+use protosocket_rpc::client::StreamConnector;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{
+    pki_types::{pem::PemObject, CertificateDer, ServerName},
+    ClientConfig, RootCertStore,
+};
+
+/// How a client verifies the server's certificate.
+#[derive(Debug, Clone)]
+pub(crate) enum TlsConfiguration {
+    /// Verify against the public web pki roots.
+    Webpki { server_name: ServerName<'static> },
+    /// Verify against your own root certificates, like a private certificate authority.
+    RootCertificates {
+        server_name: ServerName<'static>,
+        roots: Arc<RootCertStore>,
+    },
+    /// Accept any certificate. Useful for self-signed servers, but it does not authenticate them.
+    Unverified { server_name: ServerName<'static> },
+}
+
+pub(crate) fn parse_server_name(server_name: &str) -> Result<ServerName<'static>, crate::Error> {
+    ServerName::try_from(server_name.to_string())
+        .map_err(|e| crate::Error::TlsConfiguration(format!("invalid server name: {e}")))
+}
+
+pub(crate) fn parse_root_certificates(pem: &[u8]) -> Result<RootCertStore, crate::Error> {
+    let mut roots = RootCertStore::empty();
+    for certificate in CertificateDer::pem_slice_iter(pem) {
+        let certificate = certificate
+            .map_err(|e| crate::Error::TlsConfiguration(format!("invalid certificate: {e}")))?;
+        roots
+            .add(certificate)
+            .map_err(|e| crate::Error::TlsConfiguration(format!("bad root certificate: {e}")))?;
+    }
+    if roots.is_empty() {
+        return Err(crate::Error::TlsConfiguration(
+            "no certificates in root certificate pem".to_string(),
+        ));
+    }
+    Ok(roots)
+}
+
+/// A `StreamConnector` that verifies the server against a caller-provided root store.
+pub struct RootCertificatesStreamConnector {
+    connector: tokio_rustls::TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl RootCertificatesStreamConnector {
+    /// Verify the server as server_name against the root certificates in a pem file.
+    pub fn from_pem(server_name: &str, root_certificates_pem: &[u8]) -> Result<Self, crate::Error> {
+        Ok(Self::new(
+            parse_server_name(server_name)?,
+            Arc::new(parse_root_certificates(root_certificates_pem)?),
+        ))
+    }
+
+    pub(crate) fn new(server_name: ServerName<'static>, roots: Arc<RootCertStore>) -> Self {
+        let client_config =
+            ClientConfig::builder_with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        Self {
+            connector: tokio_rustls::TlsConnector::from(Arc::new(client_config)),
+            server_name,
+        }
+    }
+}
+
+impl std::fmt::Debug for RootCertificatesStreamConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RootCertificatesStreamConnector")
+            .field("server_name", &self.server_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamConnector for RootCertificatesStreamConnector {
+    type Stream = tokio_rustls::client::TlsStream<TcpStream>;
+
+    fn connect_stream(
+        &self,
+        stream: TcpStream,
+    ) -> impl Future<Output = std::io::Result<Self::Stream>> + Send {
+        self.connector
+            .clone()
+            .connect(self.server_name.clone(), stream)
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use bytes::Bytes;
 
@@ -9,6 +9,40 @@ pub enum MemstoreValue {
     Blob { value: Bytes },
     String { string: String },
     Map { map: HashMap<String, MemstoreValue> },
+    Integer { integer: i64 },
+}
+
+/// A value and the version it was written at.
+#[derive(Clone, Debug)]
+pub struct VersionedValue {
+    pub value: MemstoreValue,
+    pub version: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareAndSwapOutcome {
+    /// The value was written at this new version.
+    Swapped { version: u64 },
+    /// The key was not at the expected version. None if the key is absent.
+    Conflict { current_version: Option<u64> },
+}
+
+/// A server's part in replication.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplicationStatus {
+    /// Accepts writes, and serves replication to this many replicas.
+    Primary { replicas: u64 },
+    /// Follows a primary and rejects writes. lag is how far behind the primary it may be; it
+    /// keeps growing while the replica is disconnected.
+    Replica { state: ReplicaState, lag: Duration },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplicaState {
+    Disconnected,
+    /// Receiving the primary's items. Reads may miss until the transfer completes.
+    Transferring,
+    Following,
 }
 
 pub trait IntoKey {
@@ -101,6 +135,12 @@ where
     }
 }
 
+impl IntoValue for i64 {
+    fn into_value(self) -> rmemstore_messages::value::Kind {
+        rmemstore_messages::value::Kind::Integer(self)
+    }
+}
+
 impl IntoValue for rmemstore_messages::value::Kind {
     fn into_value(self) -> rmemstore_messages::value::Kind {
         self
@@ -127,6 +167,7 @@ impl IntoValue for MemstoreValue {
                         .collect(),
                 })
             }
+            MemstoreValue::Integer { integer } => integer.into_value(),
         }
     }
 }
@@ -149,6 +190,7 @@ impl TryFrom<rmemstore_messages::Value> for MemstoreValue {
                             .collect::<Result<_, crate::Error>>()?,
                     })
                 }
+                rmemstore_messages::value::Kind::Integer(integer) => Ok(Self::Integer { integer }),
             },
             None => Err(crate::Error::MalformedResponse("missing value kind")),
         }
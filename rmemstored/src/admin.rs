@@ -0,0 +1,83 @@
+//! A minimal http listener for operators. It serves the metrics at `/metrics`, and resizes the
+//! cache upon a `POST /max-weight?size=512mib`.
+//!
+//! It is not authenticated, so bind it to an address only operators can reach.
+
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::rmemstore_server::RMemstoreServer;
+
+/// Requests larger than this are rejected. Scrapes are small.
+const MAX_REQUEST_BYTES: usize = 8 << 10;
+
+pub async fn serve_admin(
+    listener: TcpListener,
+    server: Arc<RMemstoreServer>,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream, &server).await {
+                log::debug!("admin request from {address} failed: {e:?}");
+            }
+        });
+    }
+}
+
+async fn handle_request(mut stream: TcpStream, server: &RMemstoreServer) -> std::io::Result<()> {
+    let mut request = Vec::with_capacity(1024);
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if MAX_REQUEST_BYTES < request.len() {
+            return respond(&mut stream, "431 Request Header Fields Too Large", "").await;
+        }
+    }
+
+    let request_line = request
+        .split(|byte| *byte == b'\r')
+        .next()
+        .unwrap_or_default();
+    let mut parts = request_line.split(|byte| *byte == b' ');
+    match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => {
+            respond(&mut stream, "200 OK", &server.render_metrics()).await
+        }
+        (Some(b"POST"), Some(path)) if path.starts_with(b"/max-weight?") => {
+            match parse_size_query(&path[b"/max-weight?".len()..]) {
+                Some(cache_bytes) => {
+                    server.set_max_weight(cache_bytes);
+                    respond(&mut stream, "200 OK", "").await
+                }
+                None => respond(&mut stream, "400 Bad Request", "expected size=<size>\n").await,
+            }
+        }
+        (Some(b"GET" | b"POST"), _) => respond(&mut stream, "404 Not Found", "").await,
+        _ => respond(&mut stream, "405 Method Not Allowed", "").await,
+    }
+}
+
+/// The size in a `size=512mib` query, like `--size`.
+fn parse_size_query(query: &[u8]) -> Option<usize> {
+    let size = std::str::from_utf8(query.strip_prefix(b"size=")?).ok()?;
+    Some(parse_size::parse_size(size).ok()? as usize)
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
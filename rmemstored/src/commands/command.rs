@@ -1,5 +1,21 @@
+use rmemstore_messages::response;
+
 use crate::rmemstore_server::RMemstoreServer;
 
-pub trait Command {
-    fn run(self, server: &RMemstoreServer) -> Option<rmemstore_messages::response::Kind>;
+use super::command_error::CommandError;
+
+pub trait Command: Sized {
+    /// Ok(None) is a miss.
+    fn execute(self, server: &RMemstoreServer) -> Result<Option<response::Kind>, CommandError>;
+
+    /// Run the command, turning failures into an error response.
+    fn run(self, server: &RMemstoreServer) -> Option<response::Kind> {
+        match self.execute(server) {
+            Ok(kind) => kind,
+            Err(e) => {
+                log::debug!("command failed: {e}");
+                Some(response::Kind::Error(e.into()))
+            }
+        }
+    }
 }
@@ -0,0 +1,50 @@
+use rmemstore_messages::ErrorCode;
+
+use crate::{
+    rmemstore_server::IncrementError,
+    types::{FieldError, ValueError},
+};
+
+/// Why a command failed. Returned to the client as a response error.
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("missing command")]
+    MissingCommand,
+    #[error("{0} with no value")]
+    MissingValue(&'static str),
+    #[error("bad value: {0}")]
+    Value(#[from] ValueError),
+    #[error("{0}")]
+    Increment(#[from] IncrementError),
+    #[error("{0}")]
+    Field(#[from] FieldError),
+    #[error("this server is a replica of {0}, which takes the writes")]
+    ReadOnlyReplica(std::net::SocketAddr),
+    #[error("could not log the write: {0}")]
+    WriteNotLogged(#[from] std::io::Error),
+}
+
+impl CommandError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CommandError::MissingCommand => ErrorCode::MissingCommand,
+            CommandError::MissingValue(_) => ErrorCode::MissingValue,
+            CommandError::Value(_) => ErrorCode::InvalidValue,
+            CommandError::Increment(IncrementError::NotAnInteger) => ErrorCode::NotAnInteger,
+            CommandError::Increment(IncrementError::Overflow) => ErrorCode::IntegerOverflow,
+            CommandError::Field(FieldError::EmptyPath) => ErrorCode::EmptyFieldPath,
+            CommandError::Field(FieldError::NotAMap(_)) => ErrorCode::NotAMap,
+            CommandError::ReadOnlyReplica(_) => ErrorCode::ReadOnlyReplica,
+            CommandError::WriteNotLogged(_) => ErrorCode::WriteNotLogged,
+        }
+    }
+}
+
+impl From<CommandError> for rmemstore_messages::Error {
+    fn from(error: CommandError) -> Self {
+        rmemstore_messages::Error {
+            code: error.code().into(),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use rmemstore_messages::{response, CompareAndSwapResult};
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError, put::parse_put};
+
+impl Command for rmemstore_messages::CompareAndSwap {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let put = self
+            .put
+            .ok_or(CommandError::MissingValue("compare and swap"))?;
+        let (key, value, expires_at) = parse_put(put)?;
+        let result = match server.compare_and_swap(key, value, expires_at, self.expected_version)? {
+            Ok(version) => CompareAndSwapResult {
+                swapped: true,
+                version,
+            },
+            Err(current_version) => CompareAndSwapResult {
+                swapped: false,
+                version: current_version,
+            },
+        };
+        Ok(Some(response::Kind::CompareAndSwap(result)))
+    }
+}
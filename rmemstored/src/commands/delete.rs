@@ -0,0 +1,15 @@
+use rmemstore_messages::response;
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError};
+
+impl Command for rmemstore_messages::Delete {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let removed = server.remove(&self.key)?;
+        Ok(Some(response::Kind::Ok(removed.is_some())))
+    }
+}
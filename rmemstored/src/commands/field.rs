@@ -0,0 +1,36 @@
+use rmemstore_messages::response;
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError, put::parse_value};
+
+impl Command for rmemstore_messages::GetField {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let field = server.get_field(&self.key, &self.path)?;
+        Ok(field.map(|v| response::Kind::Value(v.into())))
+    }
+}
+
+impl Command for rmemstore_messages::SetField {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let value = parse_value("set field", self.value)?;
+        server.set_field(self.key, &self.path, value)??;
+        Ok(Some(response::Kind::Ok(true)))
+    }
+}
+
+impl Command for rmemstore_messages::DeleteField {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let removed = server.delete_field(self.key, &self.path)??;
+        Ok(Some(response::Kind::Ok(removed)))
+    }
+}
@@ -1,13 +1,25 @@
-use rmemstore_messages::response;
+use rmemstore_messages::{response, VersionedValue};
 
 use crate::{rmemstore_server::RMemstoreServer, types::MemstoreItem};
 
-use super::command::Command;
+use super::{command::Command, command_error::CommandError};
 
 impl Command for rmemstore_messages::Get {
-    fn run(self, server: &RMemstoreServer) -> Option<rmemstore_messages::response::Kind> {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
         let item = server.get(&self.key);
-        item.map(MemstoreItem::into_value)
-            .map(|v| response::Kind::Value(v.into()))
+        Ok(if self.versioned {
+            item.map(|item| {
+                response::Kind::VersionedValue(VersionedValue {
+                    version: item.version(),
+                    value: Some(item.into_value().into()),
+                })
+            })
+        } else {
+            item.map(MemstoreItem::into_value)
+                .map(|v| response::Kind::Value(v.into()))
+        })
     }
 }
@@ -0,0 +1,15 @@
+use rmemstore_messages::response;
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError};
+
+impl Command for rmemstore_messages::Increment {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let value = server.increment(self.key, self.delta)??;
+        Ok(Some(response::Kind::Integer(value)))
+    }
+}
@@ -1,3 +1,11 @@
 pub mod command;
+pub mod command_error;
+pub mod compare_and_swap;
+pub mod delete;
+pub mod field;
 pub mod get;
+pub mod increment;
+pub mod multi_get;
+pub mod multi_put;
 pub mod put;
+pub mod replication_status;
@@ -0,0 +1,22 @@
+use rmemstore_messages::{response, OptionalValue, Values};
+
+use crate::{rmemstore_server::RMemstoreServer, types::MemstoreItem};
+
+use super::{command::Command, command_error::CommandError};
+
+impl Command for rmemstore_messages::MultiGet {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let keys: Vec<&[u8]> = self.keys.iter().map(AsRef::as_ref).collect();
+        let values = server
+            .get_many(&keys)
+            .into_iter()
+            .map(|item| OptionalValue {
+                value: item.map(MemstoreItem::into_value).map(Into::into),
+            })
+            .collect();
+        Ok(Some(response::Kind::Values(Values { values })))
+    }
+}
@@ -0,0 +1,21 @@
+use rmemstore_messages::{response, Versions};
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError, put::parse_put};
+
+impl Command for rmemstore_messages::MultiPut {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        // Validate every put before applying any, so a bad entry does not leave a partial batch.
+        let items = self
+            .puts
+            .into_iter()
+            .map(parse_put)
+            .collect::<Result<Vec<_>, _>>()?;
+        let versions = server.put_many(items)?;
+        Ok(Some(response::Kind::Versions(Versions { versions })))
+    }
+}
@@ -1,26 +1,50 @@
-use rmemstore_messages::response;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::{
-    rmemstore_server::RMemstoreServer,
-    types::{MemstoreItem, MemstoreValue},
-};
+use bytes::Bytes;
+use rmemstore_messages::{put::Expiry, response};
 
-use super::command::Command;
+use crate::{rmemstore_server::RMemstoreServer, types::MemstoreValue};
+
+use super::{command::Command, command_error::CommandError};
 
 impl Command for rmemstore_messages::Put {
-    fn run(self, server: &RMemstoreServer) -> Option<rmemstore_messages::response::Kind> {
-        let Some(value) = self.value else {
-            log::error!("put with no value");
-            return None;
-        };
-        let value: MemstoreValue = match value.try_into() {
-            Ok(value) => value,
-            Err(e) => {
-                log::error!("bad value: {e:?}");
-                return None;
-            }
-        };
-        server.put(self.key, MemstoreItem::new(value));
-        Some(response::Kind::Ok(true))
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        let (key, value, expires_at) = parse_put(self)?;
+        server.put(key, value, expires_at)?;
+        Ok(Some(response::Kind::Ok(true)))
+    }
+}
+
+/// Validate a put, returning its key, value and expiration deadline.
+pub fn parse_put(
+    put: rmemstore_messages::Put,
+) -> Result<(Bytes, MemstoreValue, Option<Instant>), CommandError> {
+    let value = parse_value("put", put.value)?;
+    Ok((put.key, value, put.expiry.map(expiry_deadline)))
+}
+
+/// Validate a required value. command names the command in the error when it is missing.
+pub fn parse_value(
+    command: &'static str,
+    value: Option<rmemstore_messages::Value>,
+) -> Result<MemstoreValue, CommandError> {
+    let value = value.ok_or(CommandError::MissingValue(command))?;
+    Ok(value.try_into()?)
+}
+
+fn expiry_deadline(expiry: Expiry) -> Instant {
+    let now = Instant::now();
+    match expiry {
+        Expiry::TtlMillis(ttl) => now + Duration::from_millis(ttl),
+        Expiry::ExpiresAtUnixMillis(deadline) => {
+            let deadline = SystemTime::UNIX_EPOCH + Duration::from_millis(deadline);
+            // A deadline in the past is already expired.
+            now + deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO)
+        }
     }
 }
@@ -0,0 +1,16 @@
+use rmemstore_messages::response;
+
+use crate::rmemstore_server::RMemstoreServer;
+
+use super::{command::Command, command_error::CommandError};
+
+impl Command for rmemstore_messages::GetReplicationStatus {
+    fn execute(
+        self,
+        server: &RMemstoreServer,
+    ) -> Result<Option<rmemstore_messages::response::Kind>, CommandError> {
+        Ok(Some(response::Kind::ReplicationStatus(
+            server.replication_status(),
+        )))
+    }
+}
@@ -0,0 +1,113 @@
+//! Settings that can change while the server runs. They are read from the config file at
+//! startup, and again upon sighup.
+//!
+//! The file has one `name = value` setting per line. Blank lines and lines starting with `#`
+//! are ignored. Settings:
+//! * `size`: the cache size, like `--size`.
+
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReloadableConfig {
+    pub cache_bytes: Option<usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("could not read config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {0}: expected name = value")]
+    Syntax(usize),
+    #[error("line {0}: unknown setting {1:?}")]
+    UnknownSetting(usize, String),
+    #[error("line {0}: invalid size {1:?}")]
+    InvalidSize(usize, String),
+}
+
+impl ReloadableConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(ConfigError::Syntax(line_number))?;
+            let value = value.trim();
+            match name.trim() {
+                "size" => {
+                    let size = parse_size::parse_size(value)
+                        .map_err(|_| ConfigError::InvalidSize(line_number, value.to_string()))?;
+                    config.cache_bytes = Some(size as usize);
+                }
+                name => return Err(ConfigError::UnknownSetting(line_number, name.to_string())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigError, ReloadableConfig};
+
+    #[test]
+    fn test_empty_file() {
+        assert_eq!(
+            ReloadableConfig::parse("").expect("parses"),
+            ReloadableConfig::default()
+        );
+        assert_eq!(
+            ReloadableConfig::parse("\n  \n").expect("parses"),
+            ReloadableConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_comments_and_whitespace() {
+        let config = ReloadableConfig::parse("# the cache size\n\n  size =  2gib  \n  # done\n")
+            .expect("parses");
+        assert_eq!(config.cache_bytes, Some(2 << 30));
+        assert_eq!(
+            ReloadableConfig::parse("size=512")
+                .expect("parses")
+                .cache_bytes,
+            Some(512)
+        );
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(matches!(
+            ReloadableConfig::parse("size = 1mib\ncolor = blue\n"),
+            Err(ConfigError::UnknownSetting(2, name)) if name == "color"
+        ));
+    }
+
+    #[test]
+    fn test_bad_size() {
+        assert!(matches!(
+            ReloadableConfig::parse("size = lots"),
+            Err(ConfigError::InvalidSize(1, size)) if size == "lots"
+        ));
+        assert!(matches!(
+            ReloadableConfig::parse("size ="),
+            Err(ConfigError::InvalidSize(1, size)) if size.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_missing_equals() {
+        assert!(matches!(
+            ReloadableConfig::parse("# comment\nsize 1mib"),
+            Err(ConfigError::Syntax(2))
+        ));
+    }
+}
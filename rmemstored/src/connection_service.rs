@@ -1,13 +1,18 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Instant};
 
-use futures::{future::BoxFuture, stream::BoxStream, FutureExt};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
 use protosocket_rpc::{
     server::{ConnectionService, RpcKind},
     ProtosocketControlCode,
 };
-use rmemstore_messages::Response;
+use rmemstore_messages::{response, rpc, Response};
 
-use crate::{commands::command::Command, rmemstore_server::RMemstoreServer};
+use crate::{
+    commands::{command::Command, command_error::CommandError},
+    metrics::CommandName,
+    replication,
+    rmemstore_server::RMemstoreServer,
+};
 
 pub struct RMemstoreConnectionService {
     address: SocketAddr,
@@ -16,8 +21,74 @@ pub struct RMemstoreConnectionService {
 
 impl RMemstoreConnectionService {
     pub fn new(address: SocketAddr, server: Arc<RMemstoreServer>) -> Self {
+        server.connection_metrics().connection_opened();
         Self { address, server }
     }
+
+    /// Run a command, recording its latency and whether it failed. Replicas reject writes.
+    fn unary(
+        &self,
+        id: u64,
+        name: CommandName,
+        command: impl Command + Send + 'static,
+    ) -> RpcKind<BoxFuture<'static, Response>, BoxStream<'static, Response>> {
+        let server = self.server.clone();
+        RpcKind::Unary(
+            async move {
+                let start = Instant::now();
+                let kind = match server.primary() {
+                    Some(primary) if name.is_write() => Some(response::Kind::Error(
+                        CommandError::ReadOnlyReplica(primary).into(),
+                    )),
+                    _ => command.run(&server),
+                };
+                server.connection_metrics().command_completed(
+                    name,
+                    start.elapsed(),
+                    matches!(kind, Some(response::Kind::Error(_))),
+                );
+                Response {
+                    id,
+                    code: ProtosocketControlCode::Normal.as_u8() as u32,
+                    kind,
+                }
+            }
+            .boxed(),
+        )
+    }
+
+    /// Stream this server's items and then its writes to a replica. Replicas do not serve
+    /// replication, so a chain of replicas is not possible.
+    fn replicate(
+        &self,
+        id: u64,
+    ) -> RpcKind<BoxFuture<'static, Response>, BoxStream<'static, Response>> {
+        match self.server.primary() {
+            Some(primary) => {
+                log::warn!("{} asked a replica to replicate", self.address);
+                RpcKind::Streaming(
+                    futures::stream::once(futures::future::ready(Response {
+                        id,
+                        code: ProtosocketControlCode::Normal.as_u8() as u32,
+                        kind: Some(response::Kind::Error(
+                            CommandError::ReadOnlyReplica(primary).into(),
+                        )),
+                    }))
+                    .boxed(),
+                )
+            }
+            None => {
+                log::info!("{} started replicating", self.address);
+                RpcKind::Streaming(replication::serve_replica(self.server.clone(), id))
+            }
+        }
+    }
+}
+
+impl Drop for RMemstoreConnectionService {
+    fn drop(&mut self) {
+        self.server.connection_metrics().connection_closed();
+    }
 }
 
 impl ConnectionService for RMemstoreConnectionService {
@@ -33,39 +104,47 @@ impl ConnectionService for RMemstoreConnectionService {
         log::debug!("{} received message: {initiating_message:?}", self.address);
         let id = initiating_message.id;
         match initiating_message.command {
-            Some(command) => {
-                let server = self.server.clone();
-                match command {
-                    rmemstore_messages::rpc::Command::Put(put) => RpcKind::Unary(
-                        async move {
-                            Response {
-                                id,
-                                code: ProtosocketControlCode::Normal.as_u8() as u32,
-                                kind: put.run(&server),
-                            }
-                        }
-                        .boxed(),
-                    ),
-                    rmemstore_messages::rpc::Command::Get(get) => RpcKind::Unary(
-                        async move {
-                            Response {
-                                id,
-                                code: ProtosocketControlCode::Normal.as_u8() as u32,
-                                kind: get.run(&server),
-                            }
-                        }
-                        .boxed(),
-                    ),
+            Some(command) => match command {
+                rpc::Command::Put(put) => self.unary(id, CommandName::Put, put),
+                rpc::Command::Get(get) => self.unary(id, CommandName::Get, get),
+                rpc::Command::Delete(delete) => self.unary(id, CommandName::Delete, delete),
+                rpc::Command::CompareAndSwap(compare_and_swap) => {
+                    self.unary(id, CommandName::CompareAndSwap, compare_and_swap)
                 }
-            }
+                rpc::Command::MultiGet(multi_get) => {
+                    self.unary(id, CommandName::MultiGet, multi_get)
+                }
+                rpc::Command::MultiPut(multi_put) => {
+                    self.unary(id, CommandName::MultiPut, multi_put)
+                }
+                rpc::Command::Increment(increment) => {
+                    self.unary(id, CommandName::Increment, increment)
+                }
+                rpc::Command::GetField(get_field) => {
+                    self.unary(id, CommandName::GetField, get_field)
+                }
+                rpc::Command::SetField(set_field) => {
+                    self.unary(id, CommandName::SetField, set_field)
+                }
+                rpc::Command::DeleteField(delete_field) => {
+                    self.unary(id, CommandName::DeleteField, delete_field)
+                }
+                rpc::Command::Replicate(_) => self.replicate(id),
+                rpc::Command::GetReplicationStatus(get_replication_status) => self.unary(
+                    id,
+                    CommandName::GetReplicationStatus,
+                    get_replication_status,
+                ),
+            },
             None => {
-                log::error!("bad command: {initiating_message:?}");
+                log::debug!("{} sent an rpc with no command", self.address);
+                self.server.connection_metrics().missing_command();
                 RpcKind::Unary(
                     async move {
                         Response {
                             id,
-                            code: ProtosocketControlCode::Cancel.as_u8() as u32,
-                            kind: None,
+                            code: ProtosocketControlCode::Normal.as_u8() as u32,
+                            kind: Some(response::Kind::Error(CommandError::MissingCommand.into())),
                         }
                     }
                     .boxed(),
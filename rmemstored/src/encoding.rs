@@ -0,0 +1,167 @@
+//! The little-endian encoding of items shared by snapshots and the write log.
+//!
+//! * Bytes and strings are a u32 length followed by their bytes.
+//! * A value is a u8 kind followed by its content: 0 for a blob and 1 for a string; 2 for a
+//!   map, a u32 field count and then each field's name as a string and its value; 3 for an
+//!   i64 integer.
+//! * An expiration deadline is u64 milliseconds since the unix epoch, or 0 for none.
+
+use std::{
+    io::{self, Read},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::types::MemstoreValue;
+
+const BLOB: u8 = 0;
+const STRING: u8 = 1;
+const MAP: u8 = 2;
+const INTEGER: u8 = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Corrupt(&'static str),
+}
+
+/// Converts deadlines between Instants, which mean nothing to another process, and wall
+/// clock time. Both clocks are read once, so every deadline converts consistently.
+pub struct Clock {
+    instant: Instant,
+    system: SystemTime,
+}
+
+/// An expiration deadline read back from a file.
+pub enum Expiration {
+    Never,
+    At(Instant),
+    /// The deadline passed while the item was on disk.
+    Passed,
+}
+
+impl Clock {
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            system: SystemTime::now(),
+        }
+    }
+
+    pub fn encode(&self, expires_at: Option<Instant>) -> u64 {
+        match expires_at {
+            Some(expires_at) => {
+                let deadline = self.system + expires_at.saturating_duration_since(self.instant);
+                // Never 0, which means no expiration.
+                deadline
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(1, |since_epoch| since_epoch.as_millis() as u64)
+                    .max(1)
+            }
+            None => 0,
+        }
+    }
+
+    pub fn decode(&self, millis: u64) -> Expiration {
+        if millis == 0 {
+            return Expiration::Never;
+        }
+        let deadline = UNIX_EPOCH + Duration::from_millis(millis);
+        match deadline.duration_since(self.system) {
+            Ok(remaining) => Expiration::At(self.instant + remaining),
+            Err(_) => Expiration::Passed,
+        }
+    }
+}
+
+pub fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+pub fn write_value(buffer: &mut Vec<u8>, value: &MemstoreValue) {
+    match value {
+        MemstoreValue::Blob { value } => {
+            buffer.push(BLOB);
+            write_bytes(buffer, value);
+        }
+        MemstoreValue::String { value } => {
+            buffer.push(STRING);
+            write_bytes(buffer, value.as_bytes());
+        }
+        MemstoreValue::Map { map } => {
+            buffer.push(MAP);
+            buffer.extend_from_slice(&(map.len() as u32).to_le_bytes());
+            for (name, field) in map {
+                write_bytes(buffer, name.as_bytes());
+                write_value(buffer, field);
+            }
+        }
+        MemstoreValue::Integer { value } => {
+            buffer.push(INTEGER);
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+pub fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0; 1];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+pub fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+pub fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let length = read_u32(reader)? as usize;
+    let mut bytes = Vec::new();
+    // Read through take, so a corrupt length cannot allocate more than the file holds.
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, DecodeError> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| DecodeError::Corrupt("invalid utf-8"))
+}
+
+pub fn read_value(reader: &mut impl Read) -> Result<MemstoreValue, DecodeError> {
+    Ok(match read_u8(reader)? {
+        BLOB => MemstoreValue::Blob {
+            value: read_bytes(reader)?.into(),
+        },
+        STRING => MemstoreValue::String {
+            value: read_string(reader)?,
+        },
+        MAP => {
+            let fields = read_u32(reader)?;
+            let mut map = ahash::HashMap::default();
+            for _ in 0..fields {
+                let name = read_string(reader)?;
+                map.insert(name, read_value(reader)?);
+            }
+            MemstoreValue::Map { map }
+        }
+        INTEGER => {
+            let mut buffer = [0; 8];
+            reader.read_exact(&mut buffer)?;
+            MemstoreValue::Integer {
+                value: i64::from_le_bytes(buffer),
+            }
+        }
+        _ => return Err(DecodeError::Corrupt("unknown value kind")),
+    })
+}
@@ -1,16 +1,32 @@
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::{
+    net::SocketAddr,
+    sync::{atomic::AtomicUsize, Arc},
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
+use protosocket_rpc::client::{
+    TcpStreamConnector, UnverifiedTlsStreamConnector, WebpkiTlsStreamConnector,
+};
 use rmemstore_server::RMemstoreServer;
+use tokio_rustls::rustls::pki_types::ServerName;
 
+mod admin;
 mod commands;
+mod config;
 mod connection_service;
+mod encoding;
+mod metrics;
 mod options;
+mod replication;
 mod rmemstore_server;
+mod snapshot;
 mod socket_service;
+mod tls;
 mod types;
+mod write_log;
 
-use socket_service::RMemstoreSocketService;
+use socket_service::{PlaintextAcceptor, RMemstoreSocketService, StreamAcceptor};
 #[cfg(not(target_env = "msvc"))]
 use tikv_jemallocator::Jemalloc;
 
@@ -47,31 +63,354 @@ fn main() {
         .build()
         .expect("must be able to build worker runtime");
 
-    let server = Arc::new(RMemstoreServer::new(segments, options.cache_bytes));
+    let reloadable_config = match &options.config {
+        Some(path) => config::ReloadableConfig::load(path).expect("must be able to load config"),
+        None => Default::default(),
+    };
+    let cache_bytes = reloadable_config.cache_bytes.unwrap_or(options.cache_bytes);
+
+    let server = Arc::new(RMemstoreServer::new(
+        segments,
+        cache_bytes,
+        options.eviction_policy,
+        options.replication_backlog,
+    ));
+    // Restore before listening, so the first requests see the restored items.
+    let snapshots = options
+        .snapshot_path
+        .clone()
+        .map(|path| Arc::new(snapshot::Snapshots::new(path)));
+    let write_log_base_version = options
+        .write_log_path
+        .as_deref()
+        .map_or(0, write_log::WriteLog::base_version);
+    let mut replay_from = 0;
+    if let Some(snapshots) = &snapshots {
+        let start = Instant::now();
+        match snapshots.load(&server, write_log_base_version) {
+            Ok(snapshot::Restored::Items {
+                count,
+                next_version,
+            }) => {
+                log::info!(
+                    "restored {count} items from the snapshot in {:?}",
+                    start.elapsed()
+                );
+                replay_from = next_version;
+            }
+            Ok(restored) => log::info!("restored nothing from the snapshot: {restored:?}"),
+            Err(e) => log::error!("could not load the snapshot: {e}"),
+        }
+        if 0 < options.snapshot_interval_seconds {
+            connection_runtime.spawn(snapshot_periodically(
+                server.clone(),
+                snapshots.clone(),
+                Duration::from_secs(options.snapshot_interval_seconds),
+            ));
+        }
+    }
+    let write_log = options.write_log_path.clone().map(|path| {
+        let start = Instant::now();
+        let (write_log, replayed) = write_log::WriteLog::open(
+            path,
+            options.write_log_fsync,
+            options.write_log_compact_size as u64,
+            &server,
+            replay_from,
+        )
+        .expect("must be able to open the write log");
+        log::info!(
+            "replayed {replayed} writes from the write log in {:?}",
+            start.elapsed()
+        );
+        let write_log = Arc::new(write_log);
+        server.attach_write_log(write_log.clone());
+        connection_runtime.spawn(maintain_write_log(server.clone(), write_log.clone()));
+        write_log
+    });
+    if let Some(primary) = options.replica_of {
+        let status = Arc::new(replication::ReplicaStatus::new(primary));
+        server.become_replica(status.clone());
+        log::info!("replicating from {primary}");
+        follow_primary(&connection_runtime, &options, server.clone(), status);
+    }
+    connection_runtime.spawn(sweep_expired(
+        server.clone(),
+        Duration::from_millis(options.expiry_sweep_interval_millis),
+    ));
+    connection_runtime.spawn(shrink_to_size(server.clone()));
+
+    if let Some(admin_address) = options.admin_address {
+        // Bind up front, so a bad address fails startup.
+        let listener = connection_runtime
+            .block_on(tokio::net::TcpListener::bind(admin_address))
+            .expect("must be able to bind the admin address");
+        log::info!("serving admin on {admin_address}");
+        let server = server.clone();
+        connection_runtime.spawn(async move {
+            if let Err(e) = admin::serve_admin(listener, server).await {
+                log::error!("admin listener failed: {e:?}");
+            }
+        });
+    }
 
     let signals = signals::Signals::register().expect("must be able to register signals");
 
-    match options.run_mode {
-        options::ServerMode::Plaintext { socket_address } => {
-            let mut server = connection_runtime
-                .block_on(protosocket_rpc::server::SocketRpcServer::new(
-                    socket_address,
-                    RMemstoreSocketService::new(server),
-                ))
-                .expect("can create a server");
-            server.set_max_buffer_length(options.request_buffer_bytes);
-            log::info!("serving on {socket_address}");
-            let join_handle = connection_runtime.spawn(server);
-            connection_runtime.block_on(async move {
-                tokio::select! {
-                    _ = signals.wait_for_termination() => {
-                        log::warn!("terminal signal");
-                    }
-                    _ = join_handle => {
-                        log::warn!("server exited");
-                    }
+    let reload = {
+        let server = server.clone();
+        let config = options.config.clone();
+        move || reload_config(&server, config.as_deref())
+    };
+
+    let join_handle = match options.run_mode.clone() {
+        options::ServerMode::Plaintext { socket_address } => serve(
+            &connection_runtime,
+            &options,
+            socket_address,
+            RMemstoreSocketService::new(server.clone(), PlaintextAcceptor),
+        ),
+        options::ServerMode::Tls {
+            socket_address,
+            private_key,
+            certificate,
+        } => {
+            let acceptor = tls::acceptor_from_pem_files(&private_key, &certificate)
+                .expect("must be able to load tls certificate and private key");
+            serve(
+                &connection_runtime,
+                &options,
+                socket_address,
+                RMemstoreSocketService::new(server.clone(), acceptor),
+            )
+        }
+        options::ServerMode::SelfSigned {
+            hostname,
+            socket_address,
+        } => {
+            let acceptor = tls::self_signed_acceptor(&hostname)
+                .expect("must be able to generate a self-signed certificate");
+            log::info!("generated self-signed certificate for {hostname}");
+            serve(
+                &connection_runtime,
+                &options,
+                socket_address,
+                RMemstoreSocketService::new(server.clone(), acceptor),
+            )
+        }
+    };
+
+    connection_runtime.block_on(async move {
+        tokio::select! {
+            _ = signals.wait_for_termination(reload) => {
+                log::warn!("terminal signal");
+            }
+            _ = join_handle => {
+                log::warn!("server exited");
+            }
+        }
+    });
+    if let Some(snapshots) = &snapshots {
+        write_snapshot(&server, snapshots);
+    }
+    if let Some(write_log) = &write_log {
+        if let Err(e) = write_log.sync() {
+            log::error!("could not sync the write log: {e}");
+        }
+    }
+}
+
+/// How much a connection's read buffer grows by at a time, up to --buffer.
+const BUFFER_ALLOCATION_INCREMENT: usize = 1 << 20;
+/// Responses queued for a connection before its commands wait for it to read them.
+const QUEUED_RESPONSES: usize = 128;
+/// Connections waiting to be accepted before the kernel refuses more.
+const LISTEN_BACKLOG: u32 = 1024;
+/// Idle time before tcp keepalive probes start. None keeps the operating system's default.
+const TCP_KEEPALIVE: Option<Duration> = None;
+
+fn serve<TAcceptor: StreamAcceptor>(
+    connection_runtime: &tokio::runtime::Runtime,
+    options: &options::Options,
+    socket_address: SocketAddr,
+    socket_service: RMemstoreSocketService<TAcceptor>,
+) -> tokio::task::JoinHandle<std::io::Result<()>> {
+    let server = connection_runtime
+        .block_on(protosocket_rpc::server::SocketRpcServer::new(
+            socket_address,
+            socket_service,
+            options.request_buffer_bytes,
+            BUFFER_ALLOCATION_INCREMENT,
+            QUEUED_RESPONSES,
+            LISTEN_BACKLOG,
+            TCP_KEEPALIVE,
+        ))
+        .expect("can create a server");
+    log::info!("serving on {socket_address}");
+    connection_runtime.spawn(server)
+}
+
+/// Follow the primary, connecting with the replica tls options like a client would.
+fn follow_primary(
+    connection_runtime: &tokio::runtime::Runtime,
+    options: &options::Options,
+    server: Arc<RMemstoreServer>,
+    status: Arc<replication::ReplicaStatus>,
+) {
+    let max_message_size = options.request_buffer_bytes;
+    let Some(server_name) = &options.replica_tls_server_name else {
+        connection_runtime.spawn(replication::follow(
+            server,
+            status,
+            max_message_size,
+            TcpStreamConnector,
+        ));
+        return;
+    };
+    match &options.replica_root_certificates {
+        Some(root_certificates) => {
+            let pem = std::fs::read(root_certificates)
+                .expect("must be able to read the replica root certificates");
+            let connector = rmemstore::RootCertificatesStreamConnector::from_pem(server_name, &pem)
+                .expect("replica root certificates must be valid");
+            connection_runtime.spawn(replication::follow(
+                server,
+                status,
+                max_message_size,
+                connector,
+            ));
+        }
+        None => {
+            let server_name = ServerName::try_from(server_name.clone())
+                .expect("replica tls server name must be valid");
+            if options.replica_tls_unverified {
+                let connector = UnverifiedTlsStreamConnector::new(server_name);
+                connection_runtime.spawn(replication::follow(
+                    server,
+                    status,
+                    max_message_size,
+                    connector,
+                ));
+            } else {
+                let connector = WebpkiTlsStreamConnector::new(server_name);
+                connection_runtime.spawn(replication::follow(
+                    server,
+                    status,
+                    max_message_size,
+                    connector,
+                ));
+            }
+        }
+    }
+}
+
+async fn sweep_expired(server: Arc<RMemstoreServer>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let reclaimed = server.reclaim_expired();
+        if 0 < reclaimed {
+            log::debug!("reclaimed {reclaimed} expired items");
+        }
+    }
+}
+
+/// Items evicted from a segment per lock, while the cache shrinks to a smaller size.
+const SHRINK_BATCH: usize = 1024;
+
+async fn shrink_to_size(server: Arc<RMemstoreServer>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let mut evicted = 0;
+        loop {
+            let batch = server.shrink(SHRINK_BATCH);
+            if batch == 0 {
+                break;
+            }
+            evicted += batch;
+            // Let commands waiting on the segment locks run between batches.
+            tokio::task::yield_now().await;
+        }
+        if 0 < evicted {
+            log::info!("evicted {evicted} items to shrink the cache");
+        }
+    }
+}
+
+async fn snapshot_periodically(
+    server: Arc<RMemstoreServer>,
+    snapshots: Arc<snapshot::Snapshots>,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick is immediate, and the cache was just loaded.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let (server, snapshots) = (server.clone(), snapshots.clone());
+        // Writing blocks on file io and on each segment lock in turn.
+        if let Err(e) =
+            tokio::task::spawn_blocking(move || write_snapshot(&server, &snapshots)).await
+        {
+            log::error!("snapshot task failed: {e:?}");
+        }
+    }
+}
+
+/// Sync the write log every second, if its policy says so, and compact it when it grows.
+async fn maintain_write_log(server: Arc<RMemstoreServer>, write_log: Arc<write_log::WriteLog>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if write_log.fsync_policy() == options::FsyncPolicy::EverySecond {
+            let write_log = write_log.clone();
+            match tokio::task::spawn_blocking(move || write_log.sync()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("could not sync the write log: {e}"),
+                Err(e) => log::error!("write log sync task failed: {e:?}"),
+            }
+        }
+        if write_log.should_compact() {
+            let (server, write_log) = (server.clone(), write_log.clone());
+            // Not awaited, so syncs go on while it runs. Only one compaction runs at a time.
+            tokio::task::spawn_blocking(move || {
+                let start = Instant::now();
+                match write_log.compact(&server) {
+                    Ok(length) => log::info!(
+                        "compacted the write log to {length} bytes in {:?}",
+                        start.elapsed()
+                    ),
+                    Err(e) => log::error!("could not compact the write log: {e}"),
                 }
-            })
+            });
+        }
+    }
+}
+
+fn write_snapshot(server: &RMemstoreServer, snapshots: &snapshot::Snapshots) {
+    let start = Instant::now();
+    match snapshots.write(server) {
+        Ok(written) => log::info!("snapshot of {written} items in {:?}", start.elapsed()),
+        Err(e) => log::error!("could not write a snapshot: {e}"),
+    }
+}
+
+fn reload_config(server: &RMemstoreServer, path: Option<&std::path::Path>) {
+    let Some(path) = path else {
+        log::info!("no config to reload");
+        return;
+    };
+    match config::ReloadableConfig::load(path) {
+        Ok(config) => {
+            log::info!("reloaded config: {config:?}");
+            if let Some(cache_bytes) = config.cache_bytes {
+                server.set_max_weight(cache_bytes);
+            }
         }
+        Err(e) => log::error!("keeping the current config: {e}"),
     }
 }
@@ -0,0 +1,247 @@
+//! Counters and histograms, rendered in the Prometheus text exposition format.
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decrement(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Upper bounds of the latency buckets, in microseconds.
+const LATENCY_BUCKETS_MICROS: [u64; 13] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+/// A latency histogram with fixed buckets.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Not cumulative: each observation lands in one bucket. The last bucket is +Inf.
+    buckets: [AtomicU64; LATENCY_BUCKETS_MICROS.len() + 1],
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_MICROS.partition_point(|bound| *bound < micros);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Render the histogram's samples. labels are rendered before the le label, like
+    /// `command="get",`.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS_MICROS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}le=\"{}\"}} {cumulative}",
+                *bound as f64 / 1_000_000.0
+            );
+        }
+        cumulative += self.buckets[LATENCY_BUCKETS_MICROS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {cumulative}");
+        let sum = self.sum_nanos.load(Ordering::Relaxed) as f64 / 1_000_000_000.0;
+        let labels = labels.trim_end_matches(',');
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+        let _ = writeln!(out, "{name}_count{{{labels}}} {cumulative}");
+    }
+}
+
+/// The commands, for labeling per-command metrics.
+#[derive(Debug, Clone, Copy)]
+pub enum CommandName {
+    Put,
+    Get,
+    Delete,
+    CompareAndSwap,
+    MultiGet,
+    MultiPut,
+    Increment,
+    GetField,
+    SetField,
+    DeleteField,
+    GetReplicationStatus,
+}
+
+impl CommandName {
+    const ALL: [CommandName; 11] = [
+        CommandName::Put,
+        CommandName::Get,
+        CommandName::Delete,
+        CommandName::CompareAndSwap,
+        CommandName::MultiGet,
+        CommandName::MultiPut,
+        CommandName::Increment,
+        CommandName::GetField,
+        CommandName::SetField,
+        CommandName::DeleteField,
+        CommandName::GetReplicationStatus,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            CommandName::Put => "put",
+            CommandName::Get => "get",
+            CommandName::Delete => "delete",
+            CommandName::CompareAndSwap => "compare_and_swap",
+            CommandName::MultiGet => "multi_get",
+            CommandName::MultiPut => "multi_put",
+            CommandName::Increment => "increment",
+            CommandName::GetField => "get_field",
+            CommandName::SetField => "set_field",
+            CommandName::DeleteField => "delete_field",
+            CommandName::GetReplicationStatus => "get_replication_status",
+        }
+    }
+
+    /// Writes change items, so replicas reject them.
+    pub fn is_write(self) -> bool {
+        match self {
+            CommandName::Put
+            | CommandName::Delete
+            | CommandName::CompareAndSwap
+            | CommandName::MultiPut
+            | CommandName::Increment
+            | CommandName::SetField
+            | CommandName::DeleteField => true,
+            CommandName::Get
+            | CommandName::MultiGet
+            | CommandName::GetField
+            | CommandName::GetReplicationStatus => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CommandMetrics {
+    latency: Histogram,
+    errors: Counter,
+}
+
+/// Connection and per-command metrics, shared by every connection.
+#[derive(Debug, Default)]
+pub struct ConnectionMetrics {
+    connections_accepted: Counter,
+    connections_active: Gauge,
+    commands: [CommandMetrics; CommandName::ALL.len()],
+    missing_commands: Counter,
+}
+
+impl ConnectionMetrics {
+    pub fn connection_opened(&self) {
+        self.connections_accepted.increment();
+        self.connections_active.increment();
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections_active.decrement();
+    }
+
+    pub fn command_completed(&self, command: CommandName, latency: Duration, failed: bool) {
+        let metrics = &self.commands[command as usize];
+        metrics.latency.observe(latency);
+        if failed {
+            metrics.errors.increment();
+        }
+    }
+
+    pub fn missing_command(&self) {
+        self.missing_commands.increment();
+    }
+
+    pub fn render(&self, out: &mut String) {
+        write_counter(
+            out,
+            "rmemstore_connections_total",
+            "Connections accepted.",
+            self.connections_accepted.get(),
+        );
+        write_gauge(
+            out,
+            "rmemstore_connections_active",
+            "Connections currently open.",
+            self.connections_active.get(),
+        );
+        write_counter(
+            out,
+            "rmemstore_missing_commands_total",
+            "Rpcs received with no command.",
+            self.missing_commands.get(),
+        );
+
+        write_header(
+            out,
+            "rmemstore_command_duration_seconds",
+            "Time to run a command, by command.",
+            "histogram",
+        );
+        for command in CommandName::ALL {
+            self.commands[command as usize].latency.render(
+                out,
+                "rmemstore_command_duration_seconds",
+                &format!("command=\"{}\",", command.label()),
+            );
+        }
+        write_header(
+            out,
+            "rmemstore_command_errors_total",
+            "Commands that returned an error response, by command.",
+            "counter",
+        );
+        for command in CommandName::ALL {
+            let _ = writeln!(
+                out,
+                "rmemstore_command_errors_total{{command=\"{}\"}} {}",
+                command.label(),
+                self.commands[command as usize].errors.get()
+            );
+        }
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+pub fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    write_header(out, name, help, "counter");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+pub fn write_gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    write_header(out, name, help, "gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
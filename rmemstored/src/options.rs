@@ -1,6 +1,6 @@
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Clone, Debug)]
 #[clap(about = "Memstored service")]
@@ -20,10 +20,108 @@ pub struct Options {
     #[arg(long = "buffer", default_value = "128mib", value_parser=parse_bytes)]
     pub request_buffer_bytes: usize,
 
+    /// How often to reclaim expired items, in milliseconds
+    #[arg(long = "expiry-sweep-millis", default_value = "1000")]
+    pub expiry_sweep_interval_millis: u64,
+
+    /// Serve metrics over http at /metrics on this address, and resize the cache upon a
+    /// POST to /max-weight?size=<size>. It is not authenticated. Disabled when unset.
+    #[arg(long, value_parser = parse_address)]
+    pub admin_address: Option<SocketAddr>,
+
+    /// Read reloadable settings from this file at startup and upon sighup. Its size overrides
+    /// --size.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Load the cache from this snapshot file at startup, and snapshot it there periodically
+    /// and at shutdown. Disabled when unset.
+    #[arg(long)]
+    pub snapshot_path: Option<PathBuf>,
+
+    /// How often to snapshot the cache, in seconds. 0 only snapshots at shutdown.
+    #[arg(long = "snapshot-interval-seconds", default_value = "300")]
+    pub snapshot_interval_seconds: u64,
+
+    /// Log every write to this file, and replay it at startup on top of the snapshot.
+    /// Disabled when unset.
+    #[arg(long)]
+    pub write_log_path: Option<PathBuf>,
+
+    /// When to flush the write log to disk
+    #[arg(long, value_enum, default_value_t = FsyncPolicy::EverySecond)]
+    pub write_log_fsync: FsyncPolicy,
+
+    /// Compact the write log once it is larger than this, and twice its size after its last
+    /// compaction
+    #[arg(long, default_value = "64mib", value_parser=parse_bytes)]
+    pub write_log_compact_size: usize,
+
+    /// Run as a read replica of the primary at this address: copy its items, follow its writes
+    /// and reject writes from clients.
+    #[arg(long, value_parser = parse_address)]
+    pub replica_of: Option<SocketAddr>,
+
+    /// Connect to the primary with tls, verifying it as this name
+    #[arg(long, requires = "replica_of")]
+    pub replica_tls_server_name: Option<String>,
+
+    /// Pem file of root certificates to verify the primary against, instead of the web pki
+    /// roots
+    #[arg(long, requires = "replica_tls_server_name")]
+    pub replica_root_certificates: Option<PathBuf>,
+
+    /// Do not verify the primary's certificate, for self-signed primaries
+    #[arg(
+        long,
+        requires = "replica_tls_server_name",
+        conflicts_with = "replica_root_certificates"
+    )]
+    pub replica_tls_unverified: bool,
+
+    /// Writes a primary buffers for each replica. A replica that falls further behind starts
+    /// over with a new transfer.
+    #[arg(long, default_value = "65536")]
+    pub replication_backlog: usize,
+
+    /// How to choose the entries to evict when the cache is full
+    #[arg(long, value_enum, default_value_t = EvictionPolicy::Sieve)]
+    pub eviction_policy: EvictionPolicy,
+
     #[command(subcommand)]
     pub run_mode: ServerMode,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Spares entries read since the last sweep. A good default.
+    Sieve,
+    /// Keeps one-hit keys out of the main queue. Better for scan-heavy workloads.
+    S3Fifo,
+    /// Least recently used. A baseline; it costs more per read.
+    Lru,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Before each write is acknowledged. Slow, but no acknowledged write is lost.
+    Always,
+    /// Once a second. A crash of the machine loses up to a second of writes.
+    EverySecond,
+    /// When the operating system decides. Writes still survive a crash of the server alone.
+    Never,
+}
+
+impl EvictionPolicy {
+    pub fn build<K>(self) -> k_cache::AnyPolicy<K> {
+        match self {
+            EvictionPolicy::Sieve => k_cache::AnyPolicy::Sieve(Default::default()),
+            EvictionPolicy::S3Fifo => k_cache::AnyPolicy::S3Fifo(Default::default()),
+            EvictionPolicy::Lru => k_cache::AnyPolicy::Lru(Default::default()),
+        }
+    }
+}
+
 fn parse_bytes(s: &str) -> Result<usize, clap::Error> {
     parse_size::parse_size(s).map(|n| n as usize).map_err(|e| {
         log::error!("{e:?}");
@@ -37,29 +135,24 @@ pub enum ServerMode {
         #[arg(help = "Tcp listen port", default_value = "0.0.0.0:9466", value_parser = parse_address)]
         socket_address: SocketAddr,
     },
-    // Tls {
-    //     #[arg(
-    //         long,
-    //         help = "private key pem file",
-    //     )]
-    //     private_key: String,
-    //     #[arg(
-    //         long,
-    //         help = "public key pem file",
-    //     )]
-    //     public_key: String,
-    // },
-    // SelfSigned {
-    //     #[arg(help = "hostname to serve as")]
-    //     hostname: String,
-    // },
+    Tls {
+        #[arg(help = "Tcp listen port", default_value = "0.0.0.0:9466", value_parser = parse_address)]
+        socket_address: SocketAddr,
+        #[arg(long, help = "private key pem file")]
+        private_key: PathBuf,
+        #[arg(long, help = "certificate chain pem file")]
+        certificate: PathBuf,
+    },
+    SelfSigned {
+        #[arg(help = "hostname to serve as")]
+        hostname: String,
+        #[arg(help = "Tcp listen port", default_value = "0.0.0.0:9466", value_parser = parse_address)]
+        socket_address: SocketAddr,
+    },
 }
 
 fn parse_address(arg: &str) -> io::Result<SocketAddr> {
     std::net::ToSocketAddrs::to_socket_addrs(arg)?
         .next()
-        .ok_or(io::Error::new(
-            io::ErrorKind::Other,
-            "must pass a valid socket address",
-        ))
+        .ok_or(io::Error::other("must pass a valid socket address"))
 }
@@ -0,0 +1,390 @@
+//! Replication from a primary to read replicas.
+//!
+//! A replica sends its primary a streaming Replicate rpc. The primary answers with a full state
+//! transfer, one segment at a time, and then with each write as it happens. Writes are
+//! published under their segment's lock to a broadcast channel, which each replica's stream
+//! subscribes to before its transfer starts. So a write made during the transfer is in the
+//! transfer, after it in the stream, or both. Replicas apply a write only if it is newer than
+//! their copy of the key, which makes seeing a write twice harmless.
+//!
+//! A replica that falls more than the backlog behind is cut off. It reconnects, empties its
+//! cache and receives a new transfer.
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicI32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, StreamExt};
+use protosocket_prost::ProstSerializer;
+use protosocket_rpc::{
+    client::{Configuration, StreamConnector},
+    ProtosocketControlCode,
+};
+use rmemstore_messages::{
+    replication, replication_status::State, response, rpc, Replicate, ReplicatedItem,
+    ReplicatedRemoval, Replication, Response, Rpc,
+};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    encoding::{Clock, Expiration},
+    rmemstore_server::RMemstoreServer,
+    types::{MemstoreItem, ValueError},
+};
+
+/// How often a primary tells its replicas how current they are.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a replica waits before reconnecting to its primary.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Responses queued for a replica's connection. A slow replica holds its stream here, and its
+/// writes back up in the backlog.
+const STREAM_BUFFER: usize = 1024;
+
+/// A write, as published to replicas.
+pub enum Write {
+    Set {
+        key: Bytes,
+        item: MemstoreItem,
+        expires_at: Option<Instant>,
+    },
+    Remove {
+        key: Bytes,
+        version: u64,
+    },
+}
+
+/// A primary's writes, for the replicas following it.
+pub struct Feed {
+    writes: broadcast::Sender<Arc<Write>>,
+}
+
+impl Feed {
+    /// A replica that falls backlog writes behind is cut off.
+    pub fn new(backlog: usize) -> Self {
+        Self {
+            writes: broadcast::channel(backlog.max(1)).0,
+        }
+    }
+
+    /// Publish a write to the replicas. write is only built when a replica follows.
+    pub fn publish(&self, write: impl FnOnce() -> Write) {
+        if 0 < self.writes.receiver_count() {
+            // Fails only if the last replica went away since the count.
+            let _ = self.writes.send(Arc::new(write()));
+        }
+    }
+
+    /// The replicas following this server.
+    pub fn replicas(&self) -> usize {
+        self.writes.receiver_count()
+    }
+}
+
+/// The responses of a Replicate rpc: the server's items, then its writes.
+pub fn serve_replica(server: Arc<RMemstoreServer>, id: u64) -> BoxStream<'static, Response> {
+    // Subscribe before the transfer starts, so no write falls between the two.
+    let writes = server.feed().writes.subscribe();
+    let (responses, receiver) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(feed_replica(server, writes, responses, id));
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|response| (response, receiver))
+    })
+    .boxed()
+}
+
+/// Returns when the replica goes away or falls too far behind, which ends its stream.
+async fn feed_replica(
+    server: Arc<RMemstoreServer>,
+    mut writes: broadcast::Receiver<Arc<Write>>,
+    responses: mpsc::Sender<Response>,
+    id: u64,
+) {
+    let start = Instant::now();
+    let mut transferred = 0;
+    for index in 0..server.segment_count() {
+        // Copy the segment's items under its lock, and send them after releasing it.
+        let items: Vec<_> = server.with_segment_at(index, |segment| {
+            let clock = Clock::now();
+            segment
+                .iter()
+                .map(|(key, item)| {
+                    replicated_item(
+                        key.clone(),
+                        item.clone(),
+                        clock.encode(segment.expiration(key)),
+                    )
+                })
+                .collect()
+        });
+        transferred += items.len();
+        for item in items {
+            if !send(&responses, id, replication::Kind::Item(item)).await {
+                return;
+            }
+        }
+    }
+    let next_version = server.next_version();
+    if !send(
+        &responses,
+        id,
+        replication::Kind::TransferComplete(next_version),
+    )
+    .await
+    {
+        return;
+    }
+    log::info!(
+        "transferred {transferred} items to a replica in {:?}",
+        start.elapsed()
+    );
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        tokio::select! {
+            write = writes.recv() => match write {
+                Ok(write) => {
+                    if !send(&responses, id, replicated_write(&write)).await {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => return cut_off(skipped),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = heartbeat.tick() => {
+                let unix_millis = unix_millis(SystemTime::now());
+                // Every write published before the heartbeat goes ahead of it.
+                loop {
+                    match writes.try_recv() {
+                        Ok(write) => {
+                            if !send(&responses, id, replicated_write(&write)).await {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::TryRecvError::Empty) => break,
+                        Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                            return cut_off(skipped)
+                        }
+                        Err(broadcast::error::TryRecvError::Closed) => return,
+                    }
+                }
+                let heartbeat = rmemstore_messages::Heartbeat { unix_millis };
+                if !send(&responses, id, replication::Kind::Heartbeat(heartbeat)).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn cut_off(skipped: u64) {
+    log::warn!(
+        "a replica fell {skipped} writes behind and must start over; a larger \
+         --replication-backlog lets replicas fall further behind"
+    );
+}
+
+/// Returns false once the replica is gone.
+async fn send(responses: &mpsc::Sender<Response>, id: u64, kind: replication::Kind) -> bool {
+    let response = Response {
+        id,
+        code: ProtosocketControlCode::Normal.as_u8() as u32,
+        kind: Some(response::Kind::Replication(Replication {
+            kind: Some(kind),
+        })),
+    };
+    responses.send(response).await.is_ok()
+}
+
+fn replicated_write(write: &Write) -> replication::Kind {
+    match write {
+        Write::Set {
+            key,
+            item,
+            expires_at,
+        } => replication::Kind::Item(replicated_item(
+            key.clone(),
+            item.clone(),
+            Clock::now().encode(*expires_at),
+        )),
+        Write::Remove { key, version } => replication::Kind::Removal(ReplicatedRemoval {
+            key: key.clone(),
+            version: *version,
+        }),
+    }
+}
+
+fn replicated_item(key: Bytes, item: MemstoreItem, expires_at_unix_millis: u64) -> ReplicatedItem {
+    ReplicatedItem {
+        key,
+        version: item.version(),
+        value: Some(item.into_value().into()),
+        expires_at_unix_millis,
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis() as u64)
+}
+
+#[derive(Debug, thiserror::Error)]
+enum ReplicationError {
+    #[error("{0}")]
+    Rpc(#[from] protosocket_rpc::Error),
+    #[error("the primary refused: {0}")]
+    Refused(String),
+    #[error("malformed replication: {0}")]
+    Malformed(&'static str),
+    #[error("bad value: {0}")]
+    Value(#[from] ValueError),
+}
+
+/// Where a replica stands with its primary.
+pub struct ReplicaStatus {
+    primary: SocketAddr,
+    state: AtomicI32,
+    /// The primary's clock at the newest heartbeat applied, or this server's at startup.
+    current_as_of_unix_millis: AtomicU64,
+}
+
+impl ReplicaStatus {
+    pub fn new(primary: SocketAddr) -> Self {
+        Self {
+            primary,
+            state: AtomicI32::new(State::Disconnected.into()),
+            current_as_of_unix_millis: AtomicU64::new(unix_millis(SystemTime::now())),
+        }
+    }
+
+    pub fn primary(&self) -> SocketAddr {
+        self.primary
+    }
+
+    pub fn state(&self) -> State {
+        State::try_from(self.state.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    fn set_state(&self, state: State) {
+        self.state.store(state.into(), Ordering::Relaxed);
+    }
+
+    /// How far behind the primary this replica may be: the age of the newest heartbeat it
+    /// applied, by this server's clock.
+    pub fn lag(&self) -> Duration {
+        let current_as_of = self.current_as_of_unix_millis.load(Ordering::Relaxed);
+        Duration::from_millis(unix_millis(SystemTime::now()).saturating_sub(current_as_of))
+    }
+}
+
+/// Follow the primary for as long as the server runs, reconnecting after failures. The
+/// stream connector decides whether to connect over tls, and how to verify the primary.
+pub async fn follow<TStreamConnector: StreamConnector>(
+    server: Arc<RMemstoreServer>,
+    status: Arc<ReplicaStatus>,
+    max_message_size: usize,
+    stream_connector: TStreamConnector,
+) {
+    let mut configuration = Configuration::new(stream_connector);
+    configuration.max_buffer_length(max_message_size);
+    loop {
+        match follow_once(&server, &status, &configuration).await {
+            Ok(()) => log::warn!("{} ended replication", status.primary),
+            Err(e) => log::warn!("replication from {} failed: {e}", status.primary),
+        }
+        status.set_state(State::Disconnected);
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn follow_once<TStreamConnector: StreamConnector>(
+    server: &RMemstoreServer,
+    status: &ReplicaStatus,
+    configuration: &Configuration<TStreamConnector>,
+) -> Result<(), ReplicationError> {
+    let (client, connection) = protosocket_rpc::client::connect::<
+        ProstSerializer<Response, Rpc>,
+        ProstSerializer<Response, Rpc>,
+        TStreamConnector,
+    >(status.primary, configuration)
+    .await?;
+    tokio::spawn(connection);
+    let mut responses = client
+        .send_streaming(Rpc {
+            id: 1,
+            code: ProtosocketControlCode::Normal.as_u8() as u32,
+            command: Some(rpc::Command::Replicate(Replicate {})),
+        })
+        .await?;
+    log::info!("replicating from {}", status.primary);
+    status.set_state(State::Transferring);
+    // Otherwise keys the primary deleted while this replica was away would linger.
+    server.clear();
+
+    let start = Instant::now();
+    let mut transferred = 0;
+    while let Some(response) = responses.next().await {
+        let kind = match response {
+            Ok(Response {
+                kind: Some(response::Kind::Replication(Replication { kind: Some(kind) })),
+                ..
+            }) => kind,
+            Ok(Response {
+                kind: Some(response::Kind::Error(error)),
+                ..
+            }) => return Err(ReplicationError::Refused(error.message)),
+            Ok(_) => return Err(ReplicationError::Malformed("not a replication response")),
+            Err(protosocket_rpc::Error::Finished) => break,
+            Err(e) => return Err(e.into()),
+        };
+        match kind {
+            replication::Kind::Item(item) => {
+                apply_item(server, item)?;
+                if status.state() == State::Transferring {
+                    transferred += 1;
+                }
+            }
+            replication::Kind::Removal(removal) => {
+                server.replicate_removal(&removal.key, removal.version)
+            }
+            replication::Kind::TransferComplete(next_version) => {
+                server.advance_version(next_version);
+                status.set_state(State::Following);
+                log::info!(
+                    "received {transferred} items from {} in {:?}",
+                    status.primary,
+                    start.elapsed()
+                );
+            }
+            replication::Kind::Heartbeat(heartbeat) => status
+                .current_as_of_unix_millis
+                .store(heartbeat.unix_millis, Ordering::Relaxed),
+        }
+    }
+    Ok(())
+}
+
+fn apply_item(server: &RMemstoreServer, item: ReplicatedItem) -> Result<(), ReplicationError> {
+    let value = item
+        .value
+        .ok_or(ReplicationError::Malformed("item with no value"))?
+        .try_into()?;
+    match Clock::now().decode(item.expires_at_unix_millis) {
+        Expiration::Never => {
+            server.replicate(item.key, MemstoreItem::new(value, item.version), None)
+        }
+        Expiration::At(expires_at) => server.replicate(
+            item.key,
+            MemstoreItem::new(value, item.version),
+            Some(expires_at),
+        ),
+        // Expired in flight, but it still replaces an older copy.
+        Expiration::Passed => server.replicate_removal(&item.key, item.version),
+    }
+    Ok(())
+}
@@ -1,23 +1,508 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{atomic::AtomicU64, Arc, OnceLock},
+    time::Instant,
+};
+
 use bytes::Bytes;
 
-use crate::types::{MemstoreItem, MemstoreWeigher};
+use crate::{
+    metrics::{self, ConnectionMetrics},
+    options::EvictionPolicy,
+    replication::{Feed, ReplicaStatus, Write},
+    types::{FieldError, MemstoreItem, MemstoreValue, MemstoreWeigher},
+    write_log::WriteLog,
+};
+
+pub type Segment = k_cache::Cache<
+    Bytes,
+    MemstoreItem,
+    ahash::RandomState,
+    MemstoreWeigher,
+    k_cache::DefaultLifecycle,
+    k_cache::AnyPolicy<Bytes>,
+>;
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+pub enum IncrementError {
+    #[error("value is not an integer")]
+    NotAnInteger,
+    #[error("integer overflow")]
+    Overflow,
+}
 
 pub struct RMemstoreServer {
-    cache: k_cache::SegmentedCache<Bytes, MemstoreItem, ahash::RandomState, MemstoreWeigher>,
+    cache: k_cache::SegmentedCache<
+        Bytes,
+        MemstoreItem,
+        ahash::RandomState,
+        MemstoreWeigher,
+        k_cache::DefaultLifecycle,
+        k_cache::AnyPolicy<Bytes>,
+    >,
+    next_version: AtomicU64,
+    connection_metrics: ConnectionMetrics,
+    write_log: OnceLock<Arc<WriteLog>>,
+    feed: Feed,
+    replica: OnceLock<Arc<ReplicaStatus>>,
 }
 
 impl RMemstoreServer {
-    pub fn new(segments: usize, cache_bytes: usize) -> Self {
+    pub fn new(
+        segments: usize,
+        cache_bytes: usize,
+        eviction_policy: EvictionPolicy,
+        replication_backlog: usize,
+    ) -> Self {
         Self {
-            cache: k_cache::SegmentedCache::new(segments, cache_bytes),
+            cache: k_cache::SegmentedCache::new_with_policy(
+                segments,
+                cache_bytes,
+                k_cache::DefaultLifecycle,
+                || eviction_policy.build(),
+            ),
+            next_version: AtomicU64::new(1),
+            connection_metrics: Default::default(),
+            write_log: OnceLock::new(),
+            feed: Feed::new(replication_backlog),
+            replica: OnceLock::new(),
+        }
+    }
+
+    /// Returns the new version of the item.
+    ///
+    /// Writes fail with the write log's error when it cannot log them, and are not applied.
+    pub fn put(
+        &self,
+        key: Bytes,
+        value: MemstoreValue,
+        expires_at: Option<Instant>,
+    ) -> io::Result<u64> {
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            self.insert(segment, key, value, expires_at)
+        })
+    }
+
+    /// Put only if the current version of key is expected_version, or 0 when the key is absent.
+    /// Returns the new version when swapped, or the current version upon a conflict.
+    pub fn compare_and_swap(
+        &self,
+        key: Bytes,
+        value: MemstoreValue,
+        expires_at: Option<Instant>,
+        expected_version: u64,
+    ) -> io::Result<Result<u64, u64>> {
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            let current_version = segment.peek(&key).map_or(0, MemstoreItem::version);
+            if current_version != expected_version {
+                return Ok(Err(current_version));
+            }
+            self.insert(segment, key, value, expires_at).map(Ok)
+        })
+    }
+
+    /// Add delta to an integer item, creating it if it is absent. The item keeps its expiration
+    /// deadline. Returns the new value.
+    pub fn increment(&self, key: Bytes, delta: i64) -> io::Result<Result<i64, IncrementError>> {
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            let current = match segment.peek(&key).map(MemstoreItem::value) {
+                Some(MemstoreValue::Integer { value }) => *value,
+                Some(_) => return Ok(Err(IncrementError::NotAnInteger)),
+                None => 0,
+            };
+            let Some(value) = current.checked_add(delta) else {
+                return Ok(Err(IncrementError::Overflow));
+            };
+            let expires_at = segment.expiration(&key);
+            self.insert(segment, key, MemstoreValue::Integer { value }, expires_at)?;
+            Ok(Ok(value))
+        })
+    }
+
+    /// Get a field of a map item by its path of nested map keys.
+    pub fn get_field(
+        &self,
+        key: &[u8],
+        path: &[String],
+    ) -> Result<Option<MemstoreValue>, FieldError> {
+        if path.is_empty() {
+            return Err(FieldError::EmptyPath);
         }
+        self.cache
+            .with_segment(key, |segment| match segment.get(key) {
+                Some(item) => Ok(item.value().field(path)?.cloned()),
+                None => Ok(None),
+            })
     }
 
-    pub fn put(&self, key: Bytes, value: MemstoreItem) {
-        self.cache.put(key, value)
+    /// Set a field of a map item, creating the item and maps along the path as needed. The
+    /// item is rewritten, so its weight and version are updated, but it keeps its expiration
+    /// deadline. Returns the new version of the item.
+    pub fn set_field(
+        &self,
+        key: Bytes,
+        path: &[String],
+        field: MemstoreValue,
+    ) -> io::Result<Result<u64, FieldError>> {
+        if path.is_empty() {
+            return Ok(Err(FieldError::EmptyPath));
+        }
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            let mut value = match segment.peek(&key) {
+                Some(item) => item.value().clone(),
+                None => MemstoreValue::Map {
+                    map: Default::default(),
+                },
+            };
+            if let Err(e) = value.set_field(path, field) {
+                return Ok(Err(e));
+            }
+            let expires_at = segment.expiration(&key);
+            self.insert(segment, key, value, expires_at).map(Ok)
+        })
     }
 
+    /// Remove a field of a map item. Returns true if the field was present.
+    pub fn delete_field(
+        &self,
+        key: Bytes,
+        path: &[String],
+    ) -> io::Result<Result<bool, FieldError>> {
+        if path.is_empty() {
+            return Ok(Err(FieldError::EmptyPath));
+        }
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            let Some(item) = segment.peek(&key) else {
+                return Ok(Ok(false));
+            };
+            match item.value().field(path) {
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(Ok(false)),
+                Err(e) => return Ok(Err(e)),
+            }
+            let mut value = item.value().clone();
+            if let Err(e) = value.remove_field(path) {
+                return Ok(Err(e));
+            }
+            let expires_at = segment.expiration(&key);
+            self.insert(segment, key, value, expires_at)?;
+            Ok(Ok(true))
+        })
+    }
+
+    /// Expired items are misses, even if they have not been reclaimed yet.
     pub fn get(&self, key: &[u8]) -> Option<MemstoreItem> {
         self.cache.get(key)
     }
+
+    /// Get several items, in the order of keys.
+    pub fn get_many(&self, keys: &[&[u8]]) -> Vec<Option<MemstoreItem>> {
+        self.cache.get_many(keys)
+    }
+
+    /// Put several items, locking each segment once. Returns the new versions in the order of
+    /// items. When the write log fails, the items after the one it failed on are not put, but
+    /// the ones before it stay.
+    pub fn put_many(
+        &self,
+        items: Vec<(Bytes, MemstoreValue, Option<Instant>)>,
+    ) -> io::Result<Vec<u64>> {
+        let mut versions = vec![0; items.len()];
+        let mut result = Ok(());
+        self.cache.with_segments(
+            items.into_iter().enumerate(),
+            |(_, (key, _, _))| key,
+            |segment, (i, (key, value, expires_at))| {
+                if result.is_ok() {
+                    match self.insert(segment, key, value, expires_at) {
+                        Ok(version) => versions[i] = version,
+                        Err(e) => result = Err(e),
+                    }
+                }
+            },
+        );
+        result.map(|_| versions)
+    }
+
+    pub fn remove(&self, key: &[u8]) -> io::Result<Option<MemstoreItem>> {
+        self.cache.with_segment(key, |segment| {
+            if segment.peek(key).is_none() {
+                return Ok(None);
+            }
+            // Removals take a version too, to order them against snapshots on replay and
+            // against a replica's copy.
+            let version = self
+                .next_version
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if let Some(write_log) = self.write_log.get() {
+                write_log.log_remove(key, version)?;
+            }
+            self.feed.publish(|| Write::Remove {
+                key: Bytes::copy_from_slice(key),
+                version,
+            });
+            Ok(segment.remove(key))
+        })
+    }
+
+    /// Reclaim items whose deadline has passed. Returns the number of items removed.
+    pub fn reclaim_expired(&self) -> usize {
+        self.cache.purge_expired()
+    }
+
+    /// Change the cache's size. A smaller size is reached through shrink.
+    pub fn set_max_weight(&self, cache_bytes: usize) {
+        log::info!("setting the cache size to {cache_bytes} bytes");
+        self.cache.set_max_weight(cache_bytes);
+    }
+
+    /// Evict a batch of items from each segment that is over its size. Returns the number of
+    /// items removed; 0 once the cache fits.
+    pub fn shrink(&self, max_evictions_per_segment: usize) -> usize {
+        self.cache.shrink(max_evictions_per_segment)
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.cache.segment_count()
+    }
+
+    /// Run f on the segment at index, holding its lock.
+    pub fn with_segment_at<R>(&self, index: usize, f: impl FnOnce(&mut Segment) -> R) -> R {
+        self.cache.with_segment_at(index, f)
+    }
+
+    /// Remove every item, like before a replica's state transfer.
+    pub fn clear(&self) {
+        self.cache.clear()
+    }
+
+    /// Put an item as it was, keeping its version, like when loading a snapshot.
+    pub fn restore(&self, key: Bytes, item: MemstoreItem, expires_at: Option<Instant>) {
+        match expires_at {
+            Some(expires_at) => self.cache.put_with_expiration(key, item, expires_at),
+            None => self.cache.put(key, item),
+        }
+    }
+
+    /// Remove key without logging it, like when replaying the write log.
+    pub fn restore_removal(&self, key: &[u8]) {
+        self.cache.remove(key);
+    }
+
+    /// Apply a primary's write, unless this server has a copy of key at the same or a newer
+    /// version.
+    pub fn replicate(&self, key: Bytes, item: MemstoreItem, expires_at: Option<Instant>) {
+        let index = self.cache.segment_index(&key);
+        self.cache.with_segment_at(index, |segment| {
+            if segment
+                .peek(&key)
+                .is_some_and(|current| item.version() <= current.version())
+            {
+                return;
+            }
+            match expires_at {
+                Some(expires_at) => segment.put_with_expiration(key, item, expires_at),
+                None => segment.put(key, item),
+            }
+        })
+    }
+
+    /// Apply a primary's removal, unless this server's copy of key is newer.
+    pub fn replicate_removal(&self, key: &[u8], version: u64) {
+        self.cache.with_segment(key, |segment| {
+            segment.remove_if(key, |current| current.version() < version);
+        })
+    }
+
+    /// Follow a primary and reject writes from now on.
+    pub fn become_replica(&self, status: Arc<ReplicaStatus>) {
+        if self.replica.set(status).is_err() {
+            log::error!("already a replica");
+        }
+    }
+
+    /// The primary this server follows, or None if it is a primary itself.
+    pub fn primary(&self) -> Option<SocketAddr> {
+        self.replica.get().map(|replica| replica.primary())
+    }
+
+    pub fn feed(&self) -> &Feed {
+        &self.feed
+    }
+
+    pub fn replication_status(&self) -> rmemstore_messages::ReplicationStatus {
+        use rmemstore_messages::replication_status::{Role, State};
+
+        match self.replica.get() {
+            Some(replica) => rmemstore_messages::ReplicationStatus {
+                role: Role::Replica.into(),
+                state: replica.state().into(),
+                lag_millis: replica.lag().as_millis() as u64,
+                replicas: 0,
+            },
+            None => rmemstore_messages::ReplicationStatus {
+                role: Role::Primary.into(),
+                state: State::Serving.into(),
+                lag_millis: 0,
+                replicas: self.feed.replicas() as u64,
+            },
+        }
+    }
+
+    /// Log every write from now on. Attach the log after restoring, so the restored items
+    /// are not logged again.
+    pub fn attach_write_log(&self, write_log: Arc<WriteLog>) {
+        if self.write_log.set(write_log).is_err() {
+            log::error!("a write log is already attached");
+        }
+    }
+
+    /// The version the next write will get.
+    pub fn next_version(&self) -> u64 {
+        self.next_version.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Make sure later writes get versions of at least next_version, so versions keep
+    /// increasing across a restore.
+    pub fn advance_version(&self, next_version: u64) {
+        self.next_version
+            .fetch_max(next_version, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn connection_metrics(&self) -> &ConnectionMetrics {
+        &self.connection_metrics
+    }
+
+    /// Render the server's metrics in the Prometheus text format.
+    pub fn render_metrics(&self) -> String {
+        let stats = self.cache.stats();
+        let mut out = String::new();
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_hits_total",
+            "Lookups that found a live item, including lookups within read-modify-write commands.",
+            stats.hits,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_misses_total",
+            "Lookups that found no live item.",
+            stats.misses,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_inserts_total",
+            "Writes of absent keys.",
+            stats.inserts,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_updates_total",
+            "Writes that replaced an item.",
+            stats.updates,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_removals_total",
+            "Items deleted.",
+            stats.removals,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_evictions_total",
+            "Items evicted to make room for others.",
+            stats.evictions,
+        );
+        metrics::write_counter(
+            &mut out,
+            "rmemstore_cache_expirations_total",
+            "Expired items that were reclaimed.",
+            stats.expirations,
+        );
+        metrics::write_gauge(
+            &mut out,
+            "rmemstore_cache_weight_bytes",
+            "Approximate bytes used by items.",
+            stats.weight,
+        );
+        metrics::write_gauge(
+            &mut out,
+            "rmemstore_cache_max_weight_bytes",
+            "The configured cache size.",
+            stats.max_weight,
+        );
+        metrics::write_gauge(
+            &mut out,
+            "rmemstore_cache_items",
+            "Items in the cache, including expired items that are not yet reclaimed.",
+            stats.entries,
+        );
+        metrics::write_gauge(
+            &mut out,
+            "rmemstore_cache_policy_length",
+            "Keys the eviction policy tracks, including deleted keys that are not yet collected.",
+            stats.policy_length,
+        );
+        match self.replica.get() {
+            Some(replica) => {
+                metrics::write_gauge(
+                    &mut out,
+                    "rmemstore_replication_lag_seconds",
+                    "How far behind its primary this replica may be. It grows while disconnected.",
+                    replica.lag().as_secs_f64(),
+                );
+                metrics::write_gauge(
+                    &mut out,
+                    "rmemstore_replication_following",
+                    "1 while this replica follows its primary's writes, after its state transfer.",
+                    u8::from(
+                        replica.state() == rmemstore_messages::replication_status::State::Following,
+                    ),
+                );
+            }
+            None => metrics::write_gauge(
+                &mut out,
+                "rmemstore_replicas",
+                "Replicas following this server.",
+                self.feed.replicas(),
+            ),
+        }
+        self.connection_metrics.render(&mut out);
+        out
+    }
+
+    /// Versions are assigned, and writes are logged and published to replicas, under the
+    /// segment lock, so all follow the order of each key's writes. A write the log fails on
+    /// is neither published nor applied.
+    fn insert(
+        &self,
+        segment: &mut Segment,
+        key: Bytes,
+        value: MemstoreValue,
+        expires_at: Option<Instant>,
+    ) -> io::Result<u64> {
+        let version = self
+            .next_version
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let item = MemstoreItem::new(value, version);
+        if let Some(write_log) = self.write_log.get() {
+            write_log.log_set(&key, &item, expires_at)?;
+        }
+        self.feed.publish(|| Write::Set {
+            key: key.clone(),
+            item: item.clone(),
+            expires_at,
+        });
+        match expires_at {
+            Some(expires_at) => segment.put_with_expiration(key, item, expires_at),
+            None => segment.put(key, item),
+        }
+        Ok(version)
+    }
 }
@@ -0,0 +1,176 @@
+//! Snapshots of the cache's items in a local file, so a restarted server starts warm.
+//!
+//! The format is little-endian:
+//! * A header: the magic bytes `rmemsnap`, a u32 format version, and the u64 next version to
+//!   assign, so versions keep increasing across restarts.
+//! * Items, each prefixed by a 1 byte, and a 0 byte after the last one. An item is its key,
+//!   its u64 version, its expiration deadline, and its value, encoded as in the encoding
+//!   module.
+//!
+//! Snapshots are written to a temporary file that is renamed over the snapshot, so a crash
+//! while writing leaves the previous snapshot in place.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    encoding::{self, Clock, DecodeError, Expiration},
+    rmemstore_server::RMemstoreServer,
+    types::MemstoreItem,
+};
+
+const MAGIC: &[u8; 8] = b"rmemsnap";
+const FORMAT_VERSION: u32 = 1;
+
+const ITEM: u8 = 1;
+const END: u8 = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("snapshot io failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a snapshot file")]
+    NotASnapshot,
+    #[error("unsupported snapshot format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("corrupt snapshot: {0}")]
+    Corrupt(&'static str),
+}
+
+impl From<DecodeError> for SnapshotError {
+    fn from(error: DecodeError) -> Self {
+        match error {
+            DecodeError::Io(e) => SnapshotError::Io(e),
+            DecodeError::Corrupt(reason) => SnapshotError::Corrupt(reason),
+        }
+    }
+}
+
+/// What loading a snapshot restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restored {
+    /// There is no snapshot.
+    Nothing,
+    /// The snapshot is older than the write log's full copy of the cache, so it was skipped.
+    Superseded,
+    /// Items were restored. Every write with a version below next_version is in them.
+    Items { count: usize, next_version: u64 },
+}
+
+/// The snapshot file. Writes are serialized, so a periodic snapshot and the shutdown snapshot
+/// do not clobber each other's temporary file.
+pub struct Snapshots {
+    path: PathBuf,
+    writing: std::sync::Mutex<()>,
+}
+
+impl Snapshots {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writing: Default::default(),
+        }
+    }
+
+    /// Put the snapshot's unexpired items into server. A snapshot taken before the write log's
+    /// base version is skipped, because the log holds a newer copy of every item. If the
+    /// snapshot is corrupt, the items read before the corruption stay restored.
+    pub fn load(
+        &self,
+        server: &RMemstoreServer,
+        write_log_base_version: u64,
+    ) -> Result<Restored, SnapshotError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Restored::Nothing),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let format_version = encoding::read_u32(&mut reader)?;
+        if format_version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(format_version));
+        }
+        let next_version = encoding::read_u64(&mut reader)?;
+        if next_version <= write_log_base_version {
+            return Ok(Restored::Superseded);
+        }
+        server.advance_version(next_version);
+
+        let clock = Clock::now();
+        let mut count = 0;
+        loop {
+            match encoding::read_u8(&mut reader)? {
+                END => {
+                    return Ok(Restored::Items {
+                        count,
+                        next_version,
+                    })
+                }
+                ITEM => {}
+                _ => return Err(SnapshotError::Corrupt("bad item marker")),
+            }
+            let key = Bytes::from(encoding::read_bytes(&mut reader)?);
+            let version = encoding::read_u64(&mut reader)?;
+            let expiration = clock.decode(encoding::read_u64(&mut reader)?);
+            // Items that expired while the server was down are still read, to move past them.
+            let value = encoding::read_value(&mut reader)?;
+            let expires_at = match expiration {
+                Expiration::Never => None,
+                Expiration::At(expires_at) => Some(expires_at),
+                Expiration::Passed => continue,
+            };
+            server.restore(key, MemstoreItem::new(value, version), expires_at);
+            count += 1;
+        }
+    }
+
+    /// Write a snapshot of server's live items. Each segment is encoded under its lock, then
+    /// written out after the lock is released. Returns the number of items written.
+    pub fn write(&self, server: &RMemstoreServer) -> Result<usize, SnapshotError> {
+        let _writing = self.writing.lock().expect("mutex must not be poisoned");
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&server.next_version().to_le_bytes())?;
+
+        let clock = Clock::now();
+        let mut written = 0;
+        for index in 0..server.segment_count() {
+            let buffer = server.with_segment_at(index, |segment| {
+                let mut buffer = Vec::new();
+                for (key, item) in segment.iter() {
+                    buffer.push(ITEM);
+                    encoding::write_bytes(&mut buffer, key);
+                    buffer.extend_from_slice(&item.version().to_le_bytes());
+                    buffer.extend_from_slice(&clock.encode(segment.expiration(key)).to_le_bytes());
+                    encoding::write_value(&mut buffer, item.value());
+                    written += 1;
+                }
+                buffer
+            });
+            writer.write_all(&buffer)?;
+        }
+        writer.write_all(&[END])?;
+
+        let file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, &self.path)?;
+        Ok(written)
+    }
+}
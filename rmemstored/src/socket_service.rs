@@ -1,25 +1,65 @@
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
 use protosocket_prost::ProstSerializer;
 use protosocket_rpc::server::SocketService;
 use rmemstore_messages::{Response, Rpc};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
 
 use crate::{connection_service::RMemstoreConnectionService, rmemstore_server::RMemstoreServer};
 
-pub struct RMemstoreSocketService {
+/// Wraps accepted tcp streams, for example with tls.
+pub trait StreamAcceptor: Send + Sync + 'static {
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> impl Future<Output = std::io::Result<Self::Stream>> + Send + 'static;
+}
+
+/// Serves bare tcp.
+pub struct PlaintextAcceptor;
+impl StreamAcceptor for PlaintextAcceptor {
+    type Stream = TcpStream;
+
+    fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> impl Future<Output = std::io::Result<Self::Stream>> + Send + 'static {
+        std::future::ready(Ok(stream))
+    }
+}
+
+impl StreamAcceptor for tokio_rustls::TlsAcceptor {
+    type Stream = tokio_rustls::server::TlsStream<TcpStream>;
+
+    fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> impl Future<Output = std::io::Result<Self::Stream>> + Send + 'static {
+        tokio_rustls::TlsAcceptor::accept(self, stream)
+    }
+}
+
+pub struct RMemstoreSocketService<TAcceptor> {
     server: Arc<RMemstoreServer>,
+    acceptor: TAcceptor,
 }
 
-impl RMemstoreSocketService {
-    pub fn new(server: Arc<RMemstoreServer>) -> Self {
-        Self { server }
+impl<TAcceptor> RMemstoreSocketService<TAcceptor> {
+    pub fn new(server: Arc<RMemstoreServer>, acceptor: TAcceptor) -> Self {
+        Self { server, acceptor }
     }
 }
 
-impl SocketService for RMemstoreSocketService {
+impl<TAcceptor: StreamAcceptor> SocketService for RMemstoreSocketService<TAcceptor> {
     type RequestDeserializer = ProstSerializer<Rpc, Response>;
     type ResponseSerializer = ProstSerializer<Rpc, Response>;
     type ConnectionService = RMemstoreConnectionService;
+    type Stream = TAcceptor::Stream;
 
     fn deserializer(&self) -> Self::RequestDeserializer {
         ProstSerializer::default()
@@ -33,4 +73,11 @@ impl SocketService for RMemstoreSocketService {
         log::info!("new connection from: {address}");
         RMemstoreConnectionService::new(address, self.server.clone())
     }
+
+    fn accept_stream(
+        &self,
+        stream: TcpStream,
+    ) -> impl Future<Output = std::io::Result<Self::Stream>> + Send + 'static {
+        self.acceptor.accept(stream)
+    }
 }
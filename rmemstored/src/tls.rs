@@ -0,0 +1,38 @@
+use std::{path::Path, sync::Arc};
+
+use tokio_rustls::rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ServerConfig,
+};
+
+/// Serve with a certificate chain and private key from pem files.
+pub fn acceptor_from_pem_files(
+    private_key: &Path,
+    certificate: &Path,
+) -> Result<tokio_rustls::TlsAcceptor, Box<dyn std::error::Error>> {
+    let private_key = PrivateKeyDer::from_pem_file(private_key)?;
+    let certificates =
+        CertificateDer::pem_file_iter(certificate)?.collect::<Result<Vec<_>, _>>()?;
+    acceptor(certificates, private_key)
+}
+
+/// Serve with a new certificate for hostname, signed by itself.
+pub fn self_signed_acceptor(
+    hostname: &str,
+) -> Result<tokio_rustls::TlsAcceptor, Box<dyn std::error::Error>> {
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec![hostname.to_string()])?;
+    let private_key = PrivateKeyDer::try_from(signing_key.serialize_der())?;
+    acceptor(vec![cert.der().clone()], private_key)
+}
+
+fn acceptor(
+    certificates: Vec<CertificateDer<'static>>,
+    private_key: PrivateKeyDer<'static>,
+) -> Result<tokio_rustls::TlsAcceptor, Box<dyn std::error::Error>> {
+    let config =
+        ServerConfig::builder_with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
+            .with_no_client_auth()
+            .with_single_cert(certificates, private_key)?;
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
@@ -0,0 +1,7 @@
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum FieldError {
+    #[error("Empty field path")]
+    EmptyPath,
+    #[error("Not a map at field path {0:?}")]
+    NotAMap(String),
+}
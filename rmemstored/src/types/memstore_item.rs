@@ -1,22 +1,25 @@
-use bytes::Bytes;
-
 use super::memstore_value::MemstoreValue;
 
 #[derive(Clone, Debug)]
 pub struct MemstoreItem {
     value: MemstoreValue,
+    version: u64,
 }
 
 impl MemstoreItem {
-    pub fn new(value: MemstoreValue) -> Self {
-        Self { value }
+    pub fn new(value: MemstoreValue, version: u64) -> Self {
+        Self { value, version }
     }
 
-    pub fn into_value(self) -> MemstoreValue {
-        self.value
+    pub fn version(&self) -> u64 {
+        self.version
     }
 
-    pub fn weigher(key: &Bytes, item: &MemstoreItem) -> u32 {
-        (item.value.size() + key.len()) as u32
+    pub fn value(&self) -> &MemstoreValue {
+        &self.value
+    }
+
+    pub fn into_value(self) -> MemstoreValue {
+        self.value
     }
 }
@@ -2,23 +2,97 @@ use ahash::HashMap;
 use bytes::Bytes;
 use rmemstore_messages::Map;
 
-use super::ValueError;
+use super::{FieldError, ValueError};
 
 #[derive(Clone, Debug)]
 pub enum MemstoreValue {
     Blob { value: Bytes },
     String { value: String },
     Map { map: HashMap<String, MemstoreValue> },
+    Integer { value: i64 },
 }
 
 impl MemstoreValue {
+    /// The bytes this value holds on the heap. Its inline size is part of the cache's
+    /// per-entry overhead.
     pub fn size(&self) -> usize {
         match self {
             MemstoreValue::Blob { value } => value.len(),
-            MemstoreValue::String { value } => value.len(),
-            MemstoreValue::Map { map } => map.iter().map(|(k, v)| k.len() + v.size()).sum(),
+            MemstoreValue::String { value } => value.capacity(),
+            MemstoreValue::Map { map } => {
+                // Each field pays for its slot, its name and its value's own heap bytes.
+                map.capacity() * (size_of::<(String, MemstoreValue)>() + 1)
+                    + map
+                        .iter()
+                        .map(|(k, v)| k.capacity() + v.size())
+                        .sum::<usize>()
+            }
+            MemstoreValue::Integer { .. } => 0,
         }
     }
+
+    /// The field at path, a list of nested map keys, or None if a field along the path is
+    /// absent. Errors name the path of the value that is not a map.
+    pub fn field(&self, path: &[String]) -> Result<Option<&MemstoreValue>, FieldError> {
+        if path.is_empty() {
+            return Err(FieldError::EmptyPath);
+        }
+        let mut value = self;
+        for (depth, name) in path.iter().enumerate() {
+            let MemstoreValue::Map { map } = value else {
+                return Err(FieldError::NotAMap(path[..depth].join(".")));
+            };
+            match map.get(name) {
+                Some(field) => value = field,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Set the field at path, creating absent maps along the way.
+    pub fn set_field(&mut self, path: &[String], field: MemstoreValue) -> Result<(), FieldError> {
+        let Some((name, parents)) = path.split_last() else {
+            return Err(FieldError::EmptyPath);
+        };
+        let mut value = self;
+        for (depth, parent) in parents.iter().enumerate() {
+            let MemstoreValue::Map { map } = value else {
+                return Err(FieldError::NotAMap(path[..depth].join(".")));
+            };
+            value = map
+                .entry(parent.clone())
+                .or_insert_with(|| MemstoreValue::Map {
+                    map: Default::default(),
+                });
+        }
+        let MemstoreValue::Map { map } = value else {
+            return Err(FieldError::NotAMap(parents.join(".")));
+        };
+        map.insert(name.clone(), field);
+        Ok(())
+    }
+
+    /// Remove the field at path, returning it if it was present.
+    pub fn remove_field(&mut self, path: &[String]) -> Result<Option<MemstoreValue>, FieldError> {
+        let Some((name, parents)) = path.split_last() else {
+            return Err(FieldError::EmptyPath);
+        };
+        let mut value = self;
+        for (depth, parent) in parents.iter().enumerate() {
+            let MemstoreValue::Map { map } = value else {
+                return Err(FieldError::NotAMap(path[..depth].join(".")));
+            };
+            match map.get_mut(parent) {
+                Some(field) => value = field,
+                None => return Ok(None),
+            }
+        }
+        let MemstoreValue::Map { map } = value else {
+            return Err(FieldError::NotAMap(parents.join(".")));
+        };
+        Ok(map.remove(name))
+    }
 }
 
 impl TryFrom<rmemstore_messages::Value> for MemstoreValue {
@@ -36,6 +110,7 @@ impl TryFrom<rmemstore_messages::Value> for MemstoreValue {
                         .map(|(k, v)| TryInto::<MemstoreValue>::try_into(v).map(|v| (k, v)))
                         .collect::<Result<_, Self::Error>>()?,
                 },
+                rmemstore_messages::value::Kind::Integer(value) => MemstoreValue::Integer { value },
             }),
             None => Err(ValueError::MissingAttribute("Value kind")),
         }
@@ -51,6 +126,7 @@ impl From<MemstoreValue> for rmemstore_messages::Value {
                 MemstoreValue::Map { map } => rmemstore_messages::value::Kind::Map(Map {
                     map: map.into_iter().map(|(k, v)| (k, v.into())).collect(),
                 }),
+                MemstoreValue::Integer { value } => rmemstore_messages::value::Kind::Integer(value),
             }),
         }
     }
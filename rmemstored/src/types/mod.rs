@@ -1,8 +1,10 @@
+mod field_error;
 mod memstore_item;
 mod memstore_value;
 mod value_error;
 mod weigher;
 
+pub use field_error::FieldError;
 pub use memstore_item::MemstoreItem;
 pub use memstore_value::MemstoreValue;
 pub use value_error::ValueError;
@@ -2,10 +2,23 @@ use bytes::Bytes;
 
 use super::MemstoreItem;
 
+/// Weighs items in approximate bytes of memory, so the cache size bounds the daemon's memory.
 #[derive(Clone, Debug)]
 pub struct MemstoreWeigher;
 impl k_cache::Weigher<Bytes, MemstoreItem> for MemstoreWeigher {
-    fn weigh(key: &Bytes, item: &MemstoreItem) -> usize {
-        MemstoreItem::weigher(key, item) as usize
+    fn weigh_key(key: &Bytes) -> usize {
+        key.len()
+    }
+
+    fn weigh_value(item: &MemstoreItem) -> usize {
+        item.value().size()
+    }
+
+    fn entry_overhead(policy_slot_bytes: usize) -> usize {
+        k_cache::entry_overhead_bytes::<Bytes, MemstoreItem>(policy_slot_bytes)
+    }
+
+    fn expiration_overhead() -> usize {
+        k_cache::expiration_overhead_bytes::<Bytes>()
     }
 }
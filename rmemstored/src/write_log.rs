@@ -0,0 +1,492 @@
+//! An append-only log of writes, so writes since the last snapshot survive a restart.
+//!
+//! Each write is logged as the state it left its key in: the item it set, or the key's
+//! removal, with the write's version. Records are appended under the key's segment lock, so
+//! the log holds each key's writes in the order the cache applied them, and replaying the log
+//! in order ends with the last of them.
+//!
+//! The format is little-endian:
+//! * A header: the magic bytes `rmemwlog`, a u32 format version, and a u64 base version.
+//!   A compacted log starts with a copy of every item that was live while it was rewritten,
+//!   and its base version is the next version when the rewrite started. A log that was never
+//!   compacted has base version 0.
+//! * Records, each a u32 payload length, a u64 FNV-1a checksum of the payload, and the
+//!   payload: a u8 kind, 1 for a set or 2 for a removal; the key; the u64 version; and for a
+//!   set, the expiration deadline and the value, encoded as in the encoding module.
+//!
+//! A crash can leave a partly written record at the end of the log. Replay stops at the first
+//! record that does not check out, and truncates the log there.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    encoding::{self, Clock, DecodeError, Expiration},
+    options::FsyncPolicy,
+    rmemstore_server::RMemstoreServer,
+    types::MemstoreItem,
+};
+
+const MAGIC: &[u8; 8] = b"rmemwlog";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LENGTH: u64 = 8 + 4 + 8;
+
+const SET: u8 = 1;
+const REMOVE: u8 = 2;
+
+pub struct WriteLog {
+    path: PathBuf,
+    fsync: FsyncPolicy,
+    compact_bytes: u64,
+    state: std::sync::Mutex<LogState>,
+}
+
+struct LogState {
+    /// Shared, so a sync can run without holding the log's lock.
+    file: Arc<File>,
+    length: u64,
+    /// The length right after the last compaction. The log compacts again once it doubles.
+    compacted_length: u64,
+    /// Records appended while a compaction rewrites the log, for the rewritten log.
+    compacting: Option<Vec<u8>>,
+    unsynced: bool,
+}
+
+enum Record {
+    Set {
+        key: Bytes,
+        item: MemstoreItem,
+        expiration: Expiration,
+    },
+    Remove {
+        key: Bytes,
+        version: u64,
+    },
+}
+
+impl WriteLog {
+    /// The base version of the log at path, or 0 if there is no readable log. Items from a
+    /// snapshot taken before the base version are all in the log, with newer versions.
+    pub fn base_version(path: &Path) -> u64 {
+        match File::open(path).and_then(|mut file| read_header(&mut file)) {
+            Ok(base_version) => base_version,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => {
+                log::warn!("could not read the write log header: {e}");
+                0
+            }
+        }
+    }
+
+    /// Replay the log at path into server, then open it for appends. Only writes with a
+    /// version of at least replay_from are replayed; older ones are already in the snapshot
+    /// the server was restored from. Returns the log and the number of records replayed.
+    pub fn open(
+        path: PathBuf,
+        fsync: FsyncPolicy,
+        compact_bytes: u64,
+        server: &RMemstoreServer,
+        replay_from: u64,
+    ) -> io::Result<(Self, usize)> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let (length, replayed) = match read_header(&mut file) {
+            Ok(_) => replay(&mut file, server, replay_from)?,
+            Err(e) => {
+                if 0 < file.metadata()?.len() {
+                    log::error!("discarding a write log with a bad header: {e}");
+                }
+                file = File::create(&path)?;
+                write_header(&mut file, 0)?;
+                (HEADER_LENGTH, 0)
+            }
+        };
+        if length < file.metadata()?.len() {
+            log::warn!("truncating the write log's corrupt tail at {length} bytes");
+            file.set_len(length)?;
+        }
+        file.sync_all()?;
+        let file = File::options().append(true).open(&path)?;
+
+        let write_log = Self {
+            path,
+            fsync,
+            compact_bytes,
+            state: std::sync::Mutex::new(LogState {
+                file: Arc::new(file),
+                length,
+                compacted_length: length,
+                compacting: None,
+                unsynced: false,
+            }),
+        };
+        Ok((write_log, replayed))
+    }
+
+    /// Log that key was set to item. Call it under the key's segment lock, before applying
+    /// the write, and apply it only if it was logged.
+    pub fn log_set(
+        &self,
+        key: &[u8],
+        item: &MemstoreItem,
+        expires_at: Option<Instant>,
+    ) -> io::Result<()> {
+        let mut payload = vec![SET];
+        encoding::write_bytes(&mut payload, key);
+        payload.extend_from_slice(&item.version().to_le_bytes());
+        let expiration = expires_at.map_or(0, |expires_at| Clock::now().encode(Some(expires_at)));
+        payload.extend_from_slice(&expiration.to_le_bytes());
+        encoding::write_value(&mut payload, item.value());
+        self.append(&payload)
+    }
+
+    /// Log that key was removed by the write with version. Call it under the key's segment
+    /// lock, before removing it, and remove it only if it was logged.
+    pub fn log_remove(&self, key: &[u8], version: u64) -> io::Result<()> {
+        let mut payload = vec![REMOVE];
+        encoding::write_bytes(&mut payload, key);
+        payload.extend_from_slice(&version.to_le_bytes());
+        self.append(&payload)
+    }
+
+    /// Flush appended records to disk, if any are unsynced.
+    pub fn sync(&self) -> io::Result<()> {
+        let file = {
+            let mut state = self.state.lock().expect("mutex must not be poisoned");
+            if !state.unsynced {
+                return Ok(());
+            }
+            state.unsynced = false;
+            state.file.clone()
+        };
+        file.sync_data()
+    }
+
+    pub fn fsync_policy(&self) -> FsyncPolicy {
+        self.fsync
+    }
+
+    /// Whether the log is big enough, and has grown enough since it was last compacted, to
+    /// compact.
+    pub fn should_compact(&self) -> bool {
+        let state = self.state.lock().expect("mutex must not be poisoned");
+        state.compacting.is_none()
+            && self.compact_bytes <= state.length
+            && 2 * state.compacted_length <= state.length
+    }
+
+    /// Rewrite the log from server's live items, to drop the records they superseded. Writes
+    /// go on meanwhile; the records they append during the rewrite are copied to the end of
+    /// the rewritten log. Returns the rewritten log's length.
+    pub fn compact(&self, server: &RMemstoreServer) -> io::Result<u64> {
+        let base_version = {
+            let mut state = self.state.lock().expect("mutex must not be poisoned");
+            if state.compacting.is_some() {
+                return Err(io::Error::other("the write log is already compacting"));
+            }
+            state.compacting = Some(Vec::new());
+            // Read under the log's lock: every write with a later version appends after this,
+            // so it is copied to the rewritten log.
+            server.next_version()
+        };
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let result = self.rewrite(server, base_version, Path::new(&temporary_path));
+        if result.is_err() {
+            self.state
+                .lock()
+                .expect("mutex must not be poisoned")
+                .compacting = None;
+            let _ = std::fs::remove_file(&temporary_path);
+        }
+        result
+    }
+
+    fn rewrite(
+        &self,
+        server: &RMemstoreServer,
+        base_version: u64,
+        temporary_path: &Path,
+    ) -> io::Result<u64> {
+        let mut file = File::create(temporary_path)?;
+        write_header(&mut file, base_version)?;
+        let mut length = HEADER_LENGTH;
+
+        let clock = Clock::now();
+        // Encode each segment under its lock, and write it out after the lock is released.
+        for index in 0..server.segment_count() {
+            let buffer = server.with_segment_at(index, |segment| {
+                let mut buffer = Vec::new();
+                for (key, item) in segment.iter() {
+                    let mut payload = vec![SET];
+                    encoding::write_bytes(&mut payload, key);
+                    payload.extend_from_slice(&item.version().to_le_bytes());
+                    payload.extend_from_slice(&clock.encode(segment.expiration(key)).to_le_bytes());
+                    encoding::write_value(&mut payload, item.value());
+                    frame(&mut buffer, &payload);
+                }
+                buffer
+            });
+            length += buffer.len() as u64;
+            file.write_all(&buffer)?;
+        }
+        // Sync the bulk of the log before taking the lock, so appends only wait for the tail.
+        file.sync_data()?;
+
+        let mut state = self.state.lock().expect("mutex must not be poisoned");
+        let tail = state.compacting.take().unwrap_or_default();
+        file.write_all(&tail)?;
+        file.sync_data()?;
+        std::fs::rename(temporary_path, &self.path)?;
+        length += tail.len() as u64;
+        state.file = Arc::new(file);
+        state.length = length;
+        state.compacted_length = length;
+        state.unsynced = false;
+        Ok(length)
+    }
+
+    /// Append a record, and with FsyncPolicy::Always, sync it. Upon an error the log is cut
+    /// back to before the record, so a partly written record does not hide later ones from
+    /// replay.
+    fn append(&self, payload: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(payload.len() + 12);
+        frame(&mut record, payload);
+
+        let mut state = self.state.lock().expect("mutex must not be poisoned");
+        let result = (&*state.file)
+            .write_all(&record)
+            .and_then(|_| match self.fsync {
+                FsyncPolicy::Always => state.file.sync_data(),
+                FsyncPolicy::EverySecond | FsyncPolicy::Never => Ok(()),
+            });
+        if let Err(e) = result {
+            log::error!("could not append to the write log: {e}");
+            if let Err(e) = state.file.set_len(state.length) {
+                log::error!("could not truncate the write log's failed record: {e}");
+            }
+            return Err(e);
+        }
+        state.length += record.len() as u64;
+        if let Some(compacting) = &mut state.compacting {
+            compacting.extend_from_slice(&record);
+        }
+        if self.fsync != FsyncPolicy::Always {
+            state.unsynced = true;
+        }
+        Ok(())
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn frame(buffer: &mut Vec<u8>, payload: &[u8]) {
+    buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&checksum(payload).to_le_bytes());
+    buffer.extend_from_slice(payload);
+}
+
+fn write_header(file: &mut File, base_version: u64) -> io::Result<()> {
+    file.write_all(MAGIC)?;
+    file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    file.write_all(&base_version.to_le_bytes())
+}
+
+/// Returns the base version.
+fn read_header(file: &mut File) -> io::Result<u64> {
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a write log",
+        ));
+    }
+    let format_version = encoding::read_u32(file)?;
+    if format_version != FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported write log format version {format_version}"),
+        ));
+    }
+    encoding::read_u64(file)
+}
+
+/// Replay the records after the header. Returns the length of the log up to the last good
+/// record, and the number of records replayed.
+fn replay(file: &mut File, server: &RMemstoreServer, replay_from: u64) -> io::Result<(u64, usize)> {
+    let mut reader = BufReader::new(file);
+    let clock = Clock::now();
+    let mut length = HEADER_LENGTH;
+    let mut replayed = 0;
+    while !reader.fill_buf()?.is_empty() {
+        let record = match read_record(&mut reader, &clock) {
+            Ok((record, record_length)) => {
+                length += record_length;
+                record
+            }
+            Err(e) => {
+                log::warn!("the write log ends with a bad record: {e}");
+                break;
+            }
+        };
+        let version = match record {
+            Record::Set {
+                key,
+                item,
+                expiration,
+            } => {
+                let version = item.version();
+                if replay_from <= version {
+                    match expiration {
+                        Expiration::Never => server.restore(key, item, None),
+                        Expiration::At(expires_at) => server.restore(key, item, Some(expires_at)),
+                        // It replaced whatever came before it, then expired.
+                        Expiration::Passed => server.restore_removal(&key),
+                    }
+                    replayed += 1;
+                }
+                version
+            }
+            Record::Remove { key, version } => {
+                if replay_from <= version {
+                    server.restore_removal(&key);
+                    replayed += 1;
+                }
+                version
+            }
+        };
+        server.advance_version(version + 1);
+    }
+    Ok((length, replayed))
+}
+
+/// Returns the record and its length in the log.
+fn read_record(reader: &mut impl Read, clock: &Clock) -> Result<(Record, u64), DecodeError> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    let payload_length = u32::from_le_bytes(header[..4].try_into().expect("4 bytes"));
+    let mut payload = Vec::new();
+    reader
+        .take(u64::from(payload_length))
+        .read_to_end(&mut payload)?;
+    if payload.len() != payload_length as usize {
+        return Err(DecodeError::Corrupt("truncated record"));
+    }
+    if checksum(&payload).to_le_bytes() != header[4..] {
+        return Err(DecodeError::Corrupt("checksum mismatch"));
+    }
+
+    let mut payload_reader = &payload[..];
+    let kind = encoding::read_u8(&mut payload_reader)?;
+    let key = Bytes::from(encoding::read_bytes(&mut payload_reader)?);
+    let version = encoding::read_u64(&mut payload_reader)?;
+    let record = match kind {
+        SET => {
+            let expiration = clock.decode(encoding::read_u64(&mut payload_reader)?);
+            let value = encoding::read_value(&mut payload_reader)?;
+            Record::Set {
+                key,
+                item: MemstoreItem::new(value, version),
+                expiration,
+            }
+        }
+        REMOVE => Record::Remove { key, version },
+        _ => return Err(DecodeError::Corrupt("unknown record kind")),
+    };
+    if !payload_reader.is_empty() {
+        return Err(DecodeError::Corrupt("trailing bytes in record"));
+    }
+    Ok((record, 12 + u64::from(payload_length)))
+}
+
+// /dev/full fails every write, like a full disk.
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use super::{LogState, WriteLog, HEADER_LENGTH};
+    use crate::{
+        options::{EvictionPolicy, FsyncPolicy},
+        rmemstore_server::RMemstoreServer,
+        types::{MemstoreItem, MemstoreValue},
+    };
+
+    fn full_disk_log(fsync: FsyncPolicy) -> WriteLog {
+        let file = std::fs::File::options()
+            .append(true)
+            .open("/dev/full")
+            .expect("can open /dev/full");
+        WriteLog {
+            path: "/dev/full".into(),
+            fsync,
+            compact_bytes: u64::MAX,
+            state: std::sync::Mutex::new(LogState {
+                file: Arc::new(file),
+                length: HEADER_LENGTH,
+                compacted_length: HEADER_LENGTH,
+                compacting: None,
+                unsynced: false,
+            }),
+        }
+    }
+
+    fn string(value: &str) -> MemstoreValue {
+        MemstoreValue::String {
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_failed_append_is_returned() {
+        for fsync in [FsyncPolicy::Always, FsyncPolicy::EverySecond] {
+            let write_log = full_disk_log(fsync);
+            let item = MemstoreItem::new(string("v"), 1);
+            assert!(write_log.log_set(b"k", &item, None).is_err());
+            assert!(write_log.log_remove(b"k", 2).is_err());
+
+            let state = write_log.state.lock().expect("mutex must not be poisoned");
+            assert_eq!(state.length, HEADER_LENGTH);
+            assert!(!state.unsynced);
+        }
+    }
+
+    #[test]
+    fn test_unlogged_writes_are_not_applied() {
+        let server = RMemstoreServer::new(1, 1 << 20, EvictionPolicy::Sieve, 16);
+        server
+            .put(Bytes::from_static(b"present"), string("v"), None)
+            .expect("no log is attached yet");
+        server.attach_write_log(Arc::new(full_disk_log(FsyncPolicy::Always)));
+
+        assert!(server
+            .put(Bytes::from_static(b"absent"), string("v"), None)
+            .is_err());
+        assert!(server.get(b"absent").is_none());
+        assert!(server.increment(Bytes::from_static(b"absent"), 1).is_err());
+        assert!(server.get(b"absent").is_none());
+
+        assert!(server.remove(b"present").is_err());
+        assert!(server
+            .compare_and_swap(Bytes::from_static(b"present"), string("w"), None, 1)
+            .is_err());
+        assert_eq!(server.get(b"present").map(|item| item.version()), Some(1));
+    }
+}
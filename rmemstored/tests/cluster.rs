@@ -0,0 +1,143 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::Daemon;
+use rmemstore::{types::MemstoreValue, ClusterClient, ConnectionConfiguration};
+
+fn integer(value: Option<MemstoreValue>) -> Option<i64> {
+    match value {
+        Some(MemstoreValue::Integer { integer }) => Some(integer),
+        None => None,
+        other => panic!("expected an integer, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn keys_live_on_the_node_they_hash_to() {
+    let daemons: Vec<_> = (0..3).map(|_| Daemon::start(&[], &["plaintext"])).collect();
+    let mut nodes = HashMap::new();
+    for daemon in &daemons {
+        let client = daemon.connect(ConnectionConfiguration::default()).await;
+        nodes.insert(daemon.address, client);
+    }
+    let cluster = ClusterClient::connect(
+        daemons.iter().map(|daemon| daemon.address),
+        ConnectionConfiguration::default(),
+    )
+    .await
+    .expect("can connect to the cluster");
+
+    for i in 0..300 {
+        cluster.put(format!("key {i}"), i).await.expect("put works");
+    }
+    let mut owned = HashMap::new();
+    for i in 0..300 {
+        let key = format!("key {i}");
+        let owner = cluster.node_for(key.as_str()).expect("cluster has nodes");
+        *owned.entry(owner).or_insert(0) += 1;
+        for (address, client) in &nodes {
+            let value = integer(client.get(key.as_str()).await.expect("get works"));
+            assert_eq!(
+                value,
+                (*address == owner).then_some(i),
+                "{key} on {address}"
+            );
+        }
+    }
+    assert_eq!(owned.len(), 3, "every node owns some keys: {owned:?}");
+}
+
+#[tokio::test]
+async fn batches_split_across_nodes() {
+    let daemons: Vec<_> = (0..3).map(|_| Daemon::start(&[], &["plaintext"])).collect();
+    for daemon in &daemons {
+        daemon.connect(ConnectionConfiguration::default()).await;
+    }
+    let cluster = ClusterClient::connect(
+        daemons.iter().map(|daemon| daemon.address),
+        ConnectionConfiguration::default(),
+    )
+    .await
+    .expect("can connect to the cluster");
+
+    let versions = cluster
+        .put_many((0..100).map(|i| (format!("key {i}"), i)))
+        .await
+        .expect("put_many works");
+    assert_eq!(versions.len(), 100);
+    assert!(versions.iter().all(|version| 0 < *version));
+
+    let keys: Vec<_> = (0..110).map(|i| format!("key {i}")).collect();
+    let values = cluster.get_many(keys).await.expect("get_many works");
+    for (i, value) in values.into_iter().enumerate() {
+        let expected = (i < 100).then_some(i as i64);
+        assert_eq!(integer(value), expected, "key {i}");
+    }
+}
+
+#[tokio::test]
+async fn adding_and_removing_nodes_moves_few_keys() {
+    let daemons: Vec<_> = (0..4).map(|_| Daemon::start(&[], &["plaintext"])).collect();
+    for daemon in &daemons {
+        daemon.connect(ConnectionConfiguration::default()).await;
+    }
+    let cluster = ClusterClient::connect(
+        daemons[..3].iter().map(|daemon| daemon.address),
+        ConnectionConfiguration::default(),
+    )
+    .await
+    .expect("can connect to the cluster");
+    let keys: Vec<_> = (0..1000).map(|i| format!("key {i}")).collect();
+    cluster
+        .put_many(keys.iter().map(|key| (key.as_str(), 1)))
+        .await
+        .expect("put_many works");
+    let before: Vec<_> = keys
+        .iter()
+        .map(|key| cluster.node_for(key.as_str()))
+        .collect();
+
+    let added = daemons[3].address;
+    assert!(cluster.add_node(added).await.expect("can add a node"));
+    assert!(!cluster.add_node(added).await.expect("adding again works"));
+    assert_eq!(cluster.nodes().len(), 4);
+    let values = cluster.get_many(keys.iter().map(String::as_str)).await;
+    let mut moved = 0;
+    for ((key, before), value) in keys.iter().zip(&before).zip(values.expect("works")) {
+        let after = cluster.node_for(key.as_str());
+        if after == *before {
+            assert_eq!(integer(value), Some(1), "{key} stayed, so it still hits");
+        } else {
+            assert_eq!(after, Some(added), "{key} only moves to the new node");
+            assert_eq!(integer(value), None, "{key} moved, so it misses");
+            moved += 1;
+        }
+    }
+    assert!(
+        100 < moved && moved < 400,
+        "about a quarter of the keys move, not {moved}"
+    );
+
+    assert!(cluster.remove_node(added));
+    assert!(!cluster.remove_node(added));
+    let after: Vec<_> = keys
+        .iter()
+        .map(|key| cluster.node_for(key.as_str()))
+        .collect();
+    assert_eq!(before, after, "removing the node moves its keys back");
+    let values = cluster
+        .get_many(keys.iter().map(String::as_str))
+        .await
+        .expect("works");
+    assert!(values.into_iter().all(|value| integer(value) == Some(1)));
+}
+
+#[tokio::test]
+async fn empty_cluster_fails() {
+    let cluster = ClusterClient::connect([], ConnectionConfiguration::default())
+        .await
+        .expect("an empty cluster connects");
+    let error = cluster.get("key").await.expect_err("no node to ask");
+    assert!(matches!(error, rmemstore::Error::NoNodes), "{error:?}");
+}
@@ -0,0 +1,332 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{free_address, scrape, Daemon};
+use rmemstore::types::{CompareAndSwapOutcome, MemstoreValue};
+
+async fn plaintext_daemon() -> (Daemon, rmemstore::Client) {
+    let daemon = Daemon::start(&[], &["plaintext"]);
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    (daemon, client)
+}
+
+#[tokio::test]
+async fn delete() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    client.put("key", "value").await.expect("put works");
+    assert!(client.delete("key").await.expect("delete works"));
+    assert!(client.get("key").await.expect("get works").is_none());
+    assert!(
+        !client.delete("key").await.expect("delete works"),
+        "the key is already gone"
+    );
+    assert!(!client.delete("absent").await.expect("delete works"));
+
+    client.put("key", "again").await.expect("put works");
+    assert!(matches!(
+        client.get("key").await.expect("get works"),
+        Some(MemstoreValue::String { string }) if string == "again"
+    ));
+}
+
+/// The value of an unlabeled metric on the admin address.
+async fn metric(admin_address: std::net::SocketAddr, name: &str) -> f64 {
+    let response = scrape(admin_address, "/metrics").await;
+    response
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("missing {name} in {response}"))
+        .parse()
+        .expect("metrics are numbers")
+}
+
+#[tokio::test]
+async fn put_with_ttl() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    client
+        .put_with_ttl("short", "value", Duration::from_millis(200))
+        .await
+        .expect("put works");
+    client
+        .put_with_ttl("long", "value", Duration::from_secs(60))
+        .await
+        .expect("put works");
+    assert!(client.get("short").await.expect("get works").is_some());
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(client.get("short").await.expect("get works").is_none());
+    assert!(client.get("long").await.expect("get works").is_some());
+}
+
+#[tokio::test]
+async fn put_without_ttl_clears_the_ttl() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    client
+        .put_with_ttl("key", "expiring", Duration::from_millis(200))
+        .await
+        .expect("put works");
+    client.put("key", "forever").await.expect("put works");
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(matches!(
+        client.get("key").await.expect("get works"),
+        Some(MemstoreValue::String { string }) if string == "forever"
+    ));
+}
+
+#[tokio::test]
+async fn sweep_reclaims_expired_items() {
+    let admin_address = free_address();
+    let daemon = Daemon::start(
+        &[
+            "--admin-address",
+            &admin_address.to_string(),
+            "--expiry-sweep-millis",
+            "50",
+        ],
+        &["plaintext"],
+    );
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+
+    let before = metric(admin_address, "rmemstore_cache_weight_bytes").await;
+    client
+        .put_with_ttl("key", "x".repeat(64 << 10), Duration::from_millis(100))
+        .await
+        .expect("put works");
+    let stored = metric(admin_address, "rmemstore_cache_weight_bytes").await;
+    assert!(before + 65536.0 <= stored, "{before} then {stored}");
+
+    // Without reading the key, so only the sweep can reclaim it.
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(
+        metric(admin_address, "rmemstore_cache_expirations_total").await,
+        1.0
+    );
+    let swept = metric(admin_address, "rmemstore_cache_weight_bytes").await;
+    assert!(swept + 65536.0 <= stored, "{stored} then {swept}");
+}
+
+#[tokio::test]
+async fn compare_and_swap() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    let CompareAndSwapOutcome::Swapped { version: created } = client
+        .compare_and_swap("key", "first", None)
+        .await
+        .expect("compare and swap works")
+    else {
+        panic!("an absent key can be created");
+    };
+    assert_eq!(
+        client
+            .compare_and_swap("key", "again", None)
+            .await
+            .expect("compare and swap works"),
+        CompareAndSwapOutcome::Conflict {
+            current_version: Some(created)
+        },
+    );
+
+    let versioned = client
+        .get_versioned("key")
+        .await
+        .expect("get works")
+        .expect("key is present");
+    assert_eq!(versioned.version, created);
+
+    let CompareAndSwapOutcome::Swapped { version: updated } = client
+        .compare_and_swap("key", "second", Some(created))
+        .await
+        .expect("compare and swap works")
+    else {
+        panic!("the expected version matches");
+    };
+    assert!(created < updated);
+    assert_eq!(
+        client
+            .compare_and_swap("key", "stale", Some(created))
+            .await
+            .expect("compare and swap works"),
+        CompareAndSwapOutcome::Conflict {
+            current_version: Some(updated)
+        },
+    );
+    assert!(matches!(
+        client.get("key").await.expect("get works"),
+        Some(MemstoreValue::String { string }) if string == "second"
+    ));
+
+    // Plain puts bump the version too.
+    client.put("key", "third").await.expect("put works");
+    let versioned = client
+        .get_versioned("key")
+        .await
+        .expect("get works")
+        .expect("key is present");
+    assert!(updated < versioned.version);
+}
+
+#[tokio::test]
+async fn multi_get_and_multi_put() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    let versions = client
+        .put_many((0..20).map(|i| (format!("key{i}"), format!("value{i}"))))
+        .await
+        .expect("put_many works");
+    assert_eq!(versions.len(), 20);
+
+    let versioned = client
+        .get_versioned("key7")
+        .await
+        .expect("get works")
+        .expect("key is present");
+    assert_eq!(versioned.version, versions[7]);
+
+    let values = client
+        .get_many(["key3", "absent", "key19"])
+        .await
+        .expect("get_many works");
+    assert_eq!(values.len(), 3);
+    assert!(matches!(
+        &values[0],
+        Some(MemstoreValue::String { string }) if string == "value3"
+    ));
+    assert!(values[1].is_none());
+    assert!(matches!(
+        &values[2],
+        Some(MemstoreValue::String { string }) if string == "value19"
+    ));
+
+    assert!(client
+        .get_many(Vec::<String>::new())
+        .await
+        .expect("an empty batch works")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn increment() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    assert_eq!(client.increment("counter", 5).await.expect("works"), 5);
+    assert_eq!(client.increment("counter", -7).await.expect("works"), -2);
+    assert!(matches!(
+        client.get("counter").await.expect("get works"),
+        Some(MemstoreValue::Integer { integer: -2 })
+    ));
+
+    client.put("counter", 40_i64).await.expect("put works");
+    assert_eq!(client.increment("counter", 2).await.expect("works"), 42);
+
+    client.put("text", "not a number").await.expect("put works");
+    assert!(matches!(
+        client.increment("text", 1).await,
+        Err(rmemstore::Error::WrongType {
+            code: rmemstore::ErrorCode::NotAnInteger,
+            ..
+        })
+    ));
+
+    client.put("big", i64::MAX).await.expect("put works");
+    assert!(matches!(
+        client.increment("big", 1).await,
+        Err(rmemstore::Error::IntegerOverflow(_))
+    ));
+}
+
+#[tokio::test]
+async fn map_fields() {
+    let (_daemon, client) = plaintext_daemon().await;
+
+    client
+        .set_field("map", ["outer", "inner"], "value")
+        .await
+        .expect("set_field creates the key and the maps along the path");
+    assert!(matches!(
+        client.get_field("map", ["outer", "inner"]).await.expect("works"),
+        Some(MemstoreValue::String { string }) if string == "value"
+    ));
+    assert!(matches!(
+        client.get_field("map", ["outer"]).await.expect("works"),
+        Some(MemstoreValue::Map { map }) if map.len() == 1
+    ));
+    assert!(client
+        .get_field("map", ["outer", "absent"])
+        .await
+        .expect("works")
+        .is_none());
+
+    client
+        .set_field("map", ["sibling"], 3_i64)
+        .await
+        .expect("set_field works");
+    let Some(MemstoreValue::Map { map }) = client.get("map").await.expect("get works") else {
+        panic!("the value is a map");
+    };
+    assert_eq!(map.len(), 2);
+
+    assert!(client
+        .delete_field("map", ["outer", "inner"])
+        .await
+        .expect("works"));
+    assert!(!client
+        .delete_field("map", ["outer", "inner"])
+        .await
+        .expect("works"));
+    assert!(matches!(
+        client.get_field("map", ["outer"]).await.expect("works"),
+        Some(MemstoreValue::Map { map }) if map.is_empty()
+    ));
+
+    // Strings do not have fields.
+    client.put("text", "a string").await.expect("put works");
+    assert!(matches!(
+        client.set_field("text", ["field"], "value").await,
+        Err(rmemstore::Error::WrongType {
+            code: rmemstore::ErrorCode::NotAMap,
+            ..
+        })
+    ));
+    assert!(matches!(
+        client.get_field("map", Vec::<String>::new()).await,
+        Err(rmemstore::Error::InvalidRequest {
+            code: rmemstore::ErrorCode::EmptyFieldPath,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn every_eviction_policy_evicts() {
+    for policy in ["sieve", "s3-fifo", "lru"] {
+        let daemon = Daemon::start(&["--eviction-policy", policy], &["plaintext"]);
+        let client = daemon
+            .connect(rmemstore::ConnectionConfiguration::default())
+            .await;
+
+        // 24mib of values into a 16mib cache.
+        let value = "x".repeat(8 << 10);
+        for i in 0..3000 {
+            client
+                .put(format!("key{i}"), value.as_str())
+                .await
+                .expect("put works");
+        }
+        let present = futures::future::join_all((0..3000).map(|i| client.get(format!("key{i}"))))
+            .await
+            .into_iter()
+            .filter(|value| value.as_ref().is_ok_and(Option::is_some))
+            .count();
+        assert!(0 < present && present < 2048, "{policy} kept {present}");
+        assert!(client.get("key2999").await.expect("get works").is_some());
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    net::SocketAddr,
+    process::{Child, Command},
+    time::Duration,
+};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// An rmemstored process that is killed when dropped.
+pub struct Daemon {
+    child: Child,
+    pub address: SocketAddr,
+}
+
+impl Daemon {
+    /// Start rmemstored with the given arguments, serving on a free local port.
+    /// The listen address is appended to the run mode arguments.
+    pub fn start(options: &[&str], run_mode: &[&str]) -> Self {
+        Self::start_at(free_address(), options, run_mode)
+    }
+
+    /// Start rmemstored serving on address, like a restarted daemon would.
+    pub fn start_at(address: SocketAddr, options: &[&str], run_mode: &[&str]) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_rmemstored"))
+            .args([
+                "--size",
+                "16mib",
+                "--worker-threads",
+                "1",
+                "--log-level",
+                "error",
+            ])
+            .args(options)
+            .args(run_mode)
+            .arg(address.to_string())
+            .spawn()
+            .expect("must be able to start rmemstored");
+        Self { child, address }
+    }
+
+    /// Connect a client, retrying while the daemon starts up.
+    pub async fn connect(
+        &self,
+        configuration: rmemstore::ConnectionConfiguration,
+    ) -> rmemstore::Client {
+        let mut last_error = None;
+        for _ in 0..100 {
+            match rmemstore::Client::connect(self.address, configuration.clone()).await {
+                Ok(client) => match client.get("ready check").await {
+                    Ok(_) => return client,
+                    Err(e) => last_error = Some(e),
+                },
+                Err(e) => last_error = Some(e),
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("could not connect to rmemstored: {last_error:?}");
+    }
+
+    #[allow(dead_code)] // Not every test binary signals the daemon.
+    pub fn hangup(&self) {
+        let status = Command::new("kill")
+            .args(["-HUP", &self.child.id().to_string()])
+            .status()
+            .expect("must be able to run kill");
+        assert!(status.success(), "kill failed: {status}");
+    }
+
+    /// Stop the daemon with sigterm, and wait for it to shut down.
+    #[allow(dead_code)] // Not every test binary restarts the daemon.
+    pub fn terminate(mut self) {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .expect("must be able to run kill");
+        assert!(status.success(), "kill failed: {status}");
+        let status = self
+            .child
+            .wait()
+            .expect("must be able to wait for rmemstored");
+        assert!(status.success(), "rmemstored exited with {status}");
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn free_address() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .expect("must be able to bind a local port")
+        .local_addr()
+        .expect("listener has an address")
+}
+
+/// Send an http GET to an admin address. Returns the whole response.
+#[allow(dead_code)] // Not every test binary scrapes metrics.
+pub async fn scrape(address: SocketAddr, path: &str) -> String {
+    admin_request(address, "GET", path).await
+}
+
+/// Send an http POST to an admin address. Returns the whole response.
+#[allow(dead_code)] // Not every test binary posts to the admin address.
+pub async fn post(address: SocketAddr, path: &str) -> String {
+    admin_request(address, "POST", path).await
+}
+
+async fn admin_request(address: SocketAddr, method: &str, path: &str) -> String {
+    let mut stream = tokio::net::TcpStream::connect(address)
+        .await
+        .expect("admin address accepts connections");
+    stream
+        .write_all(
+            format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .expect("can send a request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("can read the response");
+    response
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{free_address, scrape, Daemon};
+
+#[tokio::test]
+async fn scrape_metrics() {
+    let admin_address = free_address();
+    let daemon = Daemon::start(
+        &["--admin-address", &admin_address.to_string()],
+        &["plaintext"],
+    );
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+
+    client.put("one", "value").await.expect("put works");
+    client.put("two", "value").await.expect("put works");
+    client.get("one").await.expect("get works");
+    client.get("one").await.expect("get works");
+    client.put("text", "value").await.expect("put works");
+    client
+        .increment("text", 1)
+        .await
+        .expect_err("strings cannot be incremented");
+
+    let response = scrape(admin_address, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    for expected in [
+        "# TYPE rmemstore_cache_hits_total counter",
+        // Increment's own lookup of the current value is not a client read.
+        "rmemstore_cache_hits_total 2",
+        "rmemstore_cache_inserts_total 3",
+        "rmemstore_cache_items 3",
+        "rmemstore_cache_evictions_total 0",
+        "rmemstore_connections_active 1",
+        "rmemstore_command_duration_seconds_count{command=\"put\"} 3",
+        "rmemstore_command_duration_seconds_bucket{command=\"put\",le=\"+Inf\"} 3",
+        "rmemstore_command_errors_total{command=\"increment\"} 1",
+        "rmemstore_command_errors_total{command=\"put\"} 0",
+    ] {
+        assert!(
+            response.lines().any(|line| line == expected),
+            "missing {expected:?} in {response}"
+        );
+    }
+
+    let response = scrape(admin_address, "/nothing").await;
+    assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::{future::Future, time::Duration};
+
+use common::Daemon;
+use rmemstore::{types::MemstoreValue, ConnectionConfiguration, ConnectionState};
+
+/// Retry check until it passes, for up to 5 seconds.
+async fn eventually<F: Future<Output = bool>>(what: &str, mut check: impl FnMut() -> F) {
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+#[tokio::test]
+async fn client_reconnects_after_the_server_restarts() {
+    let daemon = Daemon::start(&[], &["plaintext"]);
+    let address = daemon.address;
+    let mut configuration = ConnectionConfiguration::default();
+    configuration.reconnect_delay(Duration::from_millis(10), Duration::from_millis(100));
+    let client = daemon.connect(configuration).await;
+    client.put("key", "value").await.expect("put works");
+    assert_eq!(client.connection_state(), ConnectionState::Connected);
+
+    drop(daemon);
+    eventually("the client to notice", || async { !client.is_connected() }).await;
+    let error = client.get("key").await.expect_err("the server is down");
+    assert!(
+        matches!(error, rmemstore::Error::ConnectionBroken(_)),
+        "{error:?}"
+    );
+    eventually("reconnection attempts to fail", || async {
+        matches!(
+            client.connection_state(),
+            ConnectionState::Reconnecting { attempts } if 0 < attempts
+        )
+    })
+    .await;
+
+    let _daemon = Daemon::start_at(address, &[], &["plaintext"]);
+    eventually("the client to reconnect", || async {
+        client.is_connected()
+    })
+    .await;
+    client.put("key", 1).await.expect("put works again");
+    assert!(matches!(
+        client.get("key").await.expect("get works again"),
+        Some(MemstoreValue::Integer { integer: 1 })
+    ));
+}
+
+#[tokio::test]
+async fn clones_share_the_connection() {
+    let daemon = Daemon::start(&[], &["plaintext"]);
+    let address = daemon.address;
+    let mut configuration = ConnectionConfiguration::default();
+    configuration.reconnect_delay(Duration::from_millis(10), Duration::from_millis(100));
+    let client = daemon.connect(configuration).await;
+    let clone = client.clone();
+
+    daemon.terminate();
+    eventually("the clone to notice", || async { !clone.is_connected() }).await;
+    let _daemon = Daemon::start_at(address, &[], &["plaintext"]);
+    eventually("the client to reconnect", || async {
+        client.is_connected()
+    })
+    .await;
+    assert!(clone.is_connected());
+    clone
+        .put("key", "value")
+        .await
+        .expect("the clone works again");
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::{future::Future, time::Duration};
+
+use common::Daemon;
+use rmemstore::{
+    types::{MemstoreValue, ReplicaState, ReplicationStatus},
+    Client, ConnectionConfiguration,
+};
+
+fn start_replica(primary: &Daemon) -> Daemon {
+    Daemon::start(
+        &["--replica-of", &primary.address.to_string()],
+        &["plaintext"],
+    )
+}
+
+/// Retry check until it passes, for up to 5 seconds.
+async fn eventually<F: Future<Output = bool>>(what: &str, mut check: impl FnMut() -> F) {
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+async fn replica_state(client: &Client) -> ReplicaState {
+    match client
+        .replication_status()
+        .await
+        .expect("can get the replication status")
+    {
+        ReplicationStatus::Replica { state, .. } => state,
+        other => panic!("expected a replica, got {other:?}"),
+    }
+}
+
+async fn string(client: &Client, key: &str) -> Option<String> {
+    match client.get(key).await.expect("get works") {
+        Some(MemstoreValue::String { string }) => Some(string),
+        None => None,
+        other => panic!("expected a string, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn replica_transfers_state_then_follows_writes() {
+    let primary = Daemon::start(&[], &["plaintext"]);
+    let primary_client = primary.connect(ConnectionConfiguration::default()).await;
+    for i in 0..1000 {
+        primary_client
+            .put(format!("before {i}"), format!("value {i}"))
+            .await
+            .expect("put works");
+    }
+    primary_client
+        .put("deleted", "soon")
+        .await
+        .expect("put works");
+
+    let replica = start_replica(&primary);
+    let replica_client = replica.connect(ConnectionConfiguration::default()).await;
+    eventually("the state transfer", || async {
+        replica_state(&replica_client).await == ReplicaState::Following
+    })
+    .await;
+    for i in [0, 500, 999] {
+        assert_eq!(
+            string(&replica_client, &format!("before {i}")).await,
+            Some(format!("value {i}"))
+        );
+    }
+    assert_eq!(
+        primary_client.replication_status().await.expect("works"),
+        ReplicationStatus::Primary { replicas: 1 }
+    );
+
+    primary_client.put("after", "written").await.expect("works");
+    primary_client.delete("deleted").await.expect("works");
+    primary_client.increment("counter", 3).await.expect("works");
+    primary_client
+        .set_field("map", ["a", "b"], "nested")
+        .await
+        .expect("works");
+    eventually("the writes to replicate", || async {
+        replica_client.get("map").await.expect("works").is_some()
+    })
+    .await;
+    assert_eq!(
+        string(&replica_client, "after").await.as_deref(),
+        Some("written")
+    );
+    assert!(replica_client
+        .get("deleted")
+        .await
+        .expect("works")
+        .is_none());
+    assert!(matches!(
+        replica_client.get("counter").await.expect("works"),
+        Some(MemstoreValue::Integer { integer: 3 })
+    ));
+
+    // The replica's items keep the primary's versions.
+    let on_primary = primary_client.get_versioned("after").await.expect("works");
+    let on_replica = replica_client.get_versioned("after").await.expect("works");
+    assert_eq!(
+        on_primary.map(|value| value.version),
+        on_replica.map(|value| value.version)
+    );
+}
+
+#[tokio::test]
+async fn replica_rejects_writes() {
+    let primary = Daemon::start(&[], &["plaintext"]);
+    let replica = start_replica(&primary);
+    let client = replica.connect(ConnectionConfiguration::default()).await;
+
+    let error = client
+        .put("key", "value")
+        .await
+        .expect_err("replicas reject puts");
+    assert!(
+        matches!(error, rmemstore::Error::ReadOnlyReplica(_)),
+        "{error:?}"
+    );
+    let error = client
+        .increment("key", 1)
+        .await
+        .expect_err("replicas reject increments");
+    assert!(
+        matches!(error, rmemstore::Error::ReadOnlyReplica(_)),
+        "{error:?}"
+    );
+    assert!(client.get("key").await.expect("reads work").is_none());
+}
+
+#[tokio::test]
+async fn replica_reports_its_lag() {
+    let primary = Daemon::start(&[], &["plaintext"]);
+    let replica = start_replica(&primary);
+    let client = replica.connect(ConnectionConfiguration::default()).await;
+    eventually("the replica to catch up", || async {
+        matches!(
+            client.replication_status().await.expect("works"),
+            ReplicationStatus::Replica {
+                state: ReplicaState::Following,
+                lag,
+            } if lag < Duration::from_secs(1)
+        )
+    })
+    .await;
+
+    drop(primary);
+    eventually("the replica to notice", || async {
+        replica_state(&client).await == ReplicaState::Disconnected
+    })
+    .await;
+    // Without heartbeats, the lag grows.
+    eventually("the lag to grow", || async {
+        matches!(
+            client.replication_status().await.expect("works"),
+            ReplicationStatus::Replica { lag, .. } if Duration::from_secs(1) < lag
+        )
+    })
+    .await;
+}
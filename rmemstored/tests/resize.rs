@@ -0,0 +1,111 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use common::{free_address, post, scrape, Daemon};
+
+async fn gauge(admin_address: SocketAddr, name: &str) -> u64 {
+    let response = scrape(admin_address, "/metrics").await;
+    response
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("missing {name} in {response}"))
+        .parse()
+        .expect("gauges are integers")
+}
+
+/// Scrape until the gauge satisfies predicate, or panic after a few seconds.
+async fn wait_for_gauge(admin_address: SocketAddr, name: &str, predicate: impl Fn(u64) -> bool) {
+    let mut value = 0;
+    for _ in 0..100 {
+        value = gauge(admin_address, name).await;
+        if predicate(value) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("{name} is still {value}");
+}
+
+#[tokio::test]
+async fn posting_max_weight_shrinks_the_cache() {
+    let admin_address = free_address();
+    let daemon = Daemon::start(
+        &["--admin-address", &admin_address.to_string()],
+        &["plaintext"],
+    );
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+
+    let value = "x".repeat(4 << 10);
+    for i in 0..2000 {
+        client
+            .put(format!("key{i}"), value.as_str())
+            .await
+            .expect("put works");
+    }
+    assert!(4 << 20 < gauge(admin_address, "rmemstore_cache_weight_bytes").await);
+
+    let response = post(admin_address, "/max-weight?size=1mib").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert_eq!(
+        gauge(admin_address, "rmemstore_cache_max_weight_bytes").await,
+        1 << 20
+    );
+    wait_for_gauge(admin_address, "rmemstore_cache_weight_bytes", |weight| {
+        weight <= 1 << 20
+    })
+    .await;
+    assert!(0 < gauge(admin_address, "rmemstore_cache_evictions_total").await);
+
+    let response = post(admin_address, "/max-weight?size=lots").await;
+    assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+    assert_eq!(
+        gauge(admin_address, "rmemstore_cache_max_weight_bytes").await,
+        1 << 20
+    );
+}
+
+#[tokio::test]
+async fn sighup_reloads_the_config() {
+    let config = std::env::temp_dir().join(format!("rmemstored-test-{}.conf", std::process::id()));
+    std::fs::write(&config, "# the cache size\nsize = 8mib\n").expect("can write config");
+
+    let admin_address = free_address();
+    let daemon = Daemon::start(
+        &[
+            "--admin-address",
+            &admin_address.to_string(),
+            "--config",
+            config.to_str().expect("temp paths are utf-8"),
+        ],
+        &["plaintext"],
+    );
+    // Connecting waits for the daemon to start.
+    let _client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    assert_eq!(
+        gauge(admin_address, "rmemstore_cache_max_weight_bytes").await,
+        8 << 20
+    );
+
+    std::fs::write(&config, "size = 4mib\n").expect("can write config");
+    daemon.hangup();
+    wait_for_gauge(admin_address, "rmemstore_cache_max_weight_bytes", |size| {
+        size == 4 << 20
+    })
+    .await;
+
+    // A broken config is rejected, and the last good one stays.
+    std::fs::write(&config, "size = lots\n").expect("can write config");
+    daemon.hangup();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        gauge(admin_address, "rmemstore_cache_max_weight_bytes").await,
+        4 << 20
+    );
+
+    let _ = std::fs::remove_file(&config);
+}
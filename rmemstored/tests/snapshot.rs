@@ -0,0 +1,146 @@
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use common::Daemon;
+use rmemstore::types::MemstoreValue;
+
+/// A snapshot path of its own for each test, removed when dropped.
+struct SnapshotPath(PathBuf);
+
+impl SnapshotPath {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rmemstored-{name}-{}.snapshot", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        Self(path)
+    }
+
+    fn start(&self, interval_seconds: &str) -> Daemon {
+        Daemon::start(
+            &[
+                "--snapshot-path",
+                self.0.to_str().expect("temp paths are utf-8"),
+                "--snapshot-interval-seconds",
+                interval_seconds,
+            ],
+            &["plaintext"],
+        )
+    }
+}
+
+impl Drop for SnapshotPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[tokio::test]
+async fn shutdown_snapshot_restores_items() {
+    let path = SnapshotPath::new("shutdown");
+    let daemon = path.start("0");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    client.put("string", "value").await.expect("put works");
+    client
+        .put("blob", &b"\x00\x01"[..])
+        .await
+        .expect("put works");
+    client
+        .increment("counter", 7)
+        .await
+        .expect("increment works");
+    client
+        .set_field("map", ["outer", "inner"], "value")
+        .await
+        .expect("set_field works");
+    client
+        .put_with_ttl("lasting", "value", Duration::from_secs(3600))
+        .await
+        .expect("put works");
+    client
+        .put_with_ttl("fleeting", "value", Duration::from_millis(100))
+        .await
+        .expect("put works");
+    let version = client
+        .get_versioned("string")
+        .await
+        .expect("get works")
+        .expect("string is present")
+        .version;
+    daemon.terminate();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let daemon = path.start("0");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    let restored = client
+        .get_versioned("string")
+        .await
+        .expect("get works")
+        .expect("string is restored");
+    assert!(matches!(restored.value, MemstoreValue::String { string } if string == "value"));
+    assert_eq!(restored.version, version);
+    assert!(matches!(
+        client.get("blob").await.expect("get works"),
+        Some(MemstoreValue::Blob { value }) if value[..] == b"\x00\x01"[..]
+    ));
+    assert_eq!(client.increment("counter", 1).await.expect("works"), 8);
+    assert!(matches!(
+        client.get_field("map", ["outer", "inner"]).await.expect("works"),
+        Some(MemstoreValue::String { string }) if string == "value"
+    ));
+    assert!(client.get("lasting").await.expect("get works").is_some());
+    assert!(client.get("fleeting").await.expect("get works").is_none());
+
+    // Versions keep increasing past the restored ones.
+    client.put("string", "new").await.expect("put works");
+    let updated = client
+        .get_versioned("string")
+        .await
+        .expect("get works")
+        .expect("string is present");
+    assert!(version < updated.version);
+}
+
+#[tokio::test]
+async fn periodic_snapshot_survives_a_crash() {
+    let path = SnapshotPath::new("periodic");
+    let daemon = path.start("1");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    client.put("key", "value").await.expect("put works");
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    // Dropping the daemon kills it without a shutdown snapshot.
+    drop(daemon);
+
+    let daemon = path.start("1");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    assert!(matches!(
+        client.get("key").await.expect("get works"),
+        Some(MemstoreValue::String { string }) if string == "value"
+    ));
+}
+
+#[tokio::test]
+async fn corrupt_snapshot_starts_empty() {
+    let path = SnapshotPath::new("corrupt");
+    std::fs::write(&path.0, "not a snapshot").expect("can write the file");
+    let daemon = path.start("0");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    client.put("key", "value").await.expect("put works");
+    daemon.terminate();
+
+    let daemon = path.start("0");
+    let client = daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    assert!(client.get("key").await.expect("get works").is_some());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::Daemon;
+
+fn write_certificate(name: &str) -> (std::path::PathBuf, std::path::PathBuf, String) {
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .expect("can generate a certificate");
+    let directory = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&directory).expect("can create a temp directory");
+    let private_key = directory.join("key.pem");
+    let certificate = directory.join("certificate.pem");
+    std::fs::write(&private_key, signing_key.serialize_pem()).expect("can write the key");
+    std::fs::write(&certificate, cert.pem()).expect("can write the certificate");
+    (private_key, certificate, cert.pem())
+}
+
+#[tokio::test]
+async fn tls_with_root_certificates() {
+    let (private_key, certificate, certificate_pem) = write_certificate("tls_with_roots");
+    let daemon = Daemon::start(
+        &[],
+        &[
+            "tls",
+            "--private-key",
+            private_key.to_str().expect("utf8 path"),
+            "--certificate",
+            certificate.to_str().expect("utf8 path"),
+        ],
+    );
+
+    let mut configuration = rmemstore::ConnectionConfiguration::default();
+    configuration
+        .tls_with_root_certificates("localhost", certificate_pem.as_bytes())
+        .expect("certificate is valid");
+    let client = daemon.connect(configuration).await;
+
+    client.put("key", "value").await.expect("put works");
+    assert!(matches!(
+        client.get("key").await.expect("get works"),
+        Some(rmemstore::types::MemstoreValue::String { string }) if string == "value"
+    ));
+}
+
+#[tokio::test]
+async fn tls_rejects_untrusted_server() {
+    let (private_key, certificate, _) = write_certificate("tls_untrusted_server");
+    let (_, _, other_certificate_pem) = write_certificate("tls_untrusted_root");
+    let daemon = Daemon::start(
+        &[],
+        &[
+            "tls",
+            "--private-key",
+            private_key.to_str().expect("utf8 path"),
+            "--certificate",
+            certificate.to_str().expect("utf8 path"),
+        ],
+    );
+    // Make sure the server is up before checking that the handshake fails.
+    let mut trusting = rmemstore::ConnectionConfiguration::default();
+    trusting.tls_unverified("localhost").expect("valid name");
+    daemon.connect(trusting).await;
+
+    let mut configuration = rmemstore::ConnectionConfiguration::default();
+    configuration
+        .tls_with_root_certificates("localhost", other_certificate_pem.as_bytes())
+        .expect("certificate is valid");
+    assert!(rmemstore::Client::connect(daemon.address, configuration)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn self_signed() {
+    let daemon = Daemon::start(&[], &["self-signed", "localhost"]);
+
+    let mut configuration = rmemstore::ConnectionConfiguration::default();
+    configuration
+        .tls_unverified("localhost")
+        .expect("valid server name");
+    let client = daemon.connect(configuration).await;
+
+    client.put("key", "value").await.expect("put works");
+    assert!(client.get("key").await.expect("get works").is_some());
+}
+
+/// Whether a put on the primary reaches the plaintext replica within 5 seconds.
+async fn replicates(primary: &rmemstore::Client, replica: &Daemon) -> bool {
+    primary.put("replicated", "value").await.expect("put works");
+    let replica = replica
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await;
+    for _ in 0..100 {
+        if replica
+            .get("replicated")
+            .await
+            .expect("get works")
+            .is_some()
+        {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn replica_of_tls_primary() {
+    let (private_key, certificate, certificate_pem) = write_certificate("tls_replica");
+    let certificate = certificate.to_str().expect("utf8 path");
+    let primary = Daemon::start(
+        &[],
+        &[
+            "tls",
+            "--private-key",
+            private_key.to_str().expect("utf8 path"),
+            "--certificate",
+            certificate,
+        ],
+    );
+    let mut configuration = rmemstore::ConnectionConfiguration::default();
+    configuration
+        .tls_with_root_certificates("localhost", certificate_pem.as_bytes())
+        .expect("certificate is valid");
+    let primary_client = primary.connect(configuration).await;
+
+    let replica = Daemon::start(
+        &[
+            "--replica-of",
+            &primary.address.to_string(),
+            "--replica-tls-server-name",
+            "localhost",
+            "--replica-root-certificates",
+            certificate,
+        ],
+        &["plaintext"],
+    );
+    assert!(replicates(&primary_client, &replica).await);
+}
+
+#[tokio::test]
+async fn replica_of_self_signed_primary() {
+    let primary = Daemon::start(&[], &["self-signed", "localhost"]);
+    let mut configuration = rmemstore::ConnectionConfiguration::default();
+    configuration
+        .tls_unverified("localhost")
+        .expect("valid server name");
+    let primary_client = primary.connect(configuration).await;
+
+    let replica = Daemon::start(
+        &[
+            "--replica-of",
+            &primary.address.to_string(),
+            "--replica-tls-server-name",
+            "localhost",
+            "--replica-tls-unverified",
+        ],
+        &["plaintext"],
+    );
+    assert!(replicates(&primary_client, &replica).await);
+}
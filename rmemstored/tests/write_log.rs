@@ -0,0 +1,231 @@
+mod common;
+
+use std::{io::Write, path::PathBuf, time::Duration};
+
+use common::Daemon;
+use rmemstore::types::MemstoreValue;
+
+/// Write log and snapshot paths of their own for each test, removed when dropped.
+struct Files {
+    write_log: PathBuf,
+    snapshot: PathBuf,
+}
+
+impl Files {
+    fn new(name: &str) -> Self {
+        let path = |extension| {
+            std::env::temp_dir().join(format!(
+                "rmemstored-{name}-{}.{extension}",
+                std::process::id()
+            ))
+        };
+        let files = Self {
+            write_log: path("log"),
+            snapshot: path("snapshot"),
+        };
+        files.remove();
+        files
+    }
+
+    fn remove(&self) {
+        let _ = std::fs::remove_file(&self.write_log);
+        let _ = std::fs::remove_file(&self.snapshot);
+    }
+
+    fn start(&self, options: &[&str]) -> Daemon {
+        let mut arguments = vec![
+            "--write-log-path",
+            self.write_log.to_str().expect("temp paths are utf-8"),
+        ];
+        arguments.extend_from_slice(options);
+        Daemon::start(&arguments, &["plaintext"])
+    }
+
+    fn start_with_snapshots(&self, options: &[&str]) -> Daemon {
+        let mut arguments = vec![
+            "--snapshot-path",
+            self.snapshot.to_str().expect("temp paths are utf-8"),
+            "--snapshot-interval-seconds",
+            "0",
+        ];
+        arguments.extend_from_slice(options);
+        self.start(&arguments)
+    }
+}
+
+impl Drop for Files {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+async fn connect(daemon: &Daemon) -> rmemstore::Client {
+    daemon
+        .connect(rmemstore::ConnectionConfiguration::default())
+        .await
+}
+
+fn is_string(value: Option<MemstoreValue>, expected: &str) -> bool {
+    matches!(value, Some(MemstoreValue::String { string }) if string == expected)
+}
+
+#[tokio::test]
+async fn writes_survive_a_crash() {
+    let files = Files::new("crash");
+    let daemon = files.start(&["--write-log-fsync", "always"]);
+    let client = connect(&daemon).await;
+    client.put("kept", "value").await.expect("put works");
+    client.put("deleted", "value").await.expect("put works");
+    assert!(client.delete("deleted").await.expect("delete works"));
+    client.increment("counter", 2).await.expect("works");
+    client.increment("counter", 3).await.expect("works");
+    client
+        .set_field("map", ["field"], "value")
+        .await
+        .expect("set_field works");
+    client
+        .put_with_ttl("fleeting", "value", Duration::from_millis(100))
+        .await
+        .expect("put works");
+    let version = client
+        .get_versioned("kept")
+        .await
+        .expect("get works")
+        .expect("kept is present")
+        .version;
+    // Dropping the daemon kills it without a chance to flush anything.
+    drop(daemon);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let daemon = files.start(&[]);
+    let client = connect(&daemon).await;
+    let kept = client
+        .get_versioned("kept")
+        .await
+        .expect("get works")
+        .expect("kept is replayed");
+    assert_eq!(kept.version, version);
+    assert!(client.get("deleted").await.expect("get works").is_none());
+    assert_eq!(client.increment("counter", 0).await.expect("works"), 5);
+    assert!(is_string(
+        client.get_field("map", ["field"]).await.expect("works"),
+        "value"
+    ));
+    assert!(client.get("fleeting").await.expect("get works").is_none());
+}
+
+#[tokio::test]
+async fn corrupt_tail_is_truncated() {
+    let files = Files::new("corrupt");
+    let daemon = files.start(&[]);
+    let client = connect(&daemon).await;
+    client.put("before", "value").await.expect("put works");
+    daemon.terminate();
+
+    // A record cut short by a crash.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&files.write_log)
+        .expect("the write log exists")
+        .write_all(&[200, 0, 0, 0, 1, 2, 3])
+        .expect("can append to the write log");
+
+    let daemon = files.start(&[]);
+    let client = connect(&daemon).await;
+    assert!(is_string(
+        client.get("before").await.expect("works"),
+        "value"
+    ));
+    client.put("after", "value").await.expect("put works");
+    daemon.terminate();
+
+    // Writes after the truncation are replayed too.
+    let daemon = files.start(&[]);
+    let client = connect(&daemon).await;
+    assert!(is_string(
+        client.get("before").await.expect("works"),
+        "value"
+    ));
+    assert!(is_string(
+        client.get("after").await.expect("works"),
+        "value"
+    ));
+}
+
+#[tokio::test]
+async fn log_replays_on_top_of_the_snapshot() {
+    let files = Files::new("snapshot");
+    let daemon = files.start_with_snapshots(&[]);
+    let client = connect(&daemon).await;
+    client.put("snapshotted", "value").await.expect("put works");
+    client.put("overwritten", "old").await.expect("put works");
+    daemon.terminate();
+
+    let daemon = files.start_with_snapshots(&[]);
+    let client = connect(&daemon).await;
+    assert!(client.delete("snapshotted").await.expect("delete works"));
+    client.put("overwritten", "new").await.expect("put works");
+    client.put("logged", "value").await.expect("put works");
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    // Killed, so the snapshot is older than the last writes.
+    drop(daemon);
+
+    let daemon = files.start_with_snapshots(&[]);
+    let client = connect(&daemon).await;
+    assert!(client.get("snapshotted").await.expect("works").is_none());
+    assert!(is_string(
+        client.get("overwritten").await.expect("works"),
+        "new"
+    ));
+    assert!(is_string(
+        client.get("logged").await.expect("works"),
+        "value"
+    ));
+}
+
+#[tokio::test]
+async fn compaction_shrinks_the_log() {
+    let files = Files::new("compaction");
+    let daemon = files.start_with_snapshots(&[]);
+    let client = connect(&daemon).await;
+    client.put("snapshotted", "value").await.expect("put works");
+    daemon.terminate();
+
+    let daemon = files.start_with_snapshots(&["--write-log-compact-size", "256kib"]);
+    let client = connect(&daemon).await;
+    assert!(client.delete("snapshotted").await.expect("delete works"));
+    let value = "x".repeat(4 << 10);
+    for i in 0..200 {
+        client
+            .put(format!("key{}", i % 10), format!("{i}{value}"))
+            .await
+            .expect("put works");
+    }
+    let mut length = 0;
+    for _ in 0..100 {
+        length = std::fs::metadata(&files.write_log)
+            .expect("the write log exists")
+            .len();
+        if length < 128 << 10 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(length < 128 << 10, "the write log is still {length} bytes");
+    client.put("after", "value").await.expect("put works");
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    drop(daemon);
+
+    // The compacted log is newer than the snapshot, which still holds the deleted key.
+    let daemon = files.start_with_snapshots(&[]);
+    let client = connect(&daemon).await;
+    assert!(client.get("snapshotted").await.expect("works").is_none());
+    assert!(is_string(
+        client.get("key9").await.expect("works"),
+        &format!("199{value}")
+    ));
+    assert!(is_string(
+        client.get("after").await.expect("works"),
+        "value"
+    ));
+}
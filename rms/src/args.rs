@@ -1,10 +1,26 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, num::ParseIntError, path::PathBuf, time::Duration};
 
 #[derive(clap::Parser, Debug, Clone)]
 pub struct Args {
     #[arg(long, default_value = "127.0.0.1:9466", env = "HOST")]
     pub host: SocketAddr,
 
+    /// Connect with tls, verifying the server as this name
+    #[arg(long, env = "TLS_SERVER_NAME")]
+    pub tls_server_name: Option<String>,
+
+    /// Pem file of root certificates to verify the server against, instead of the web pki roots
+    #[arg(long, requires = "tls_server_name")]
+    pub root_certificates: Option<PathBuf>,
+
+    /// Do not verify the server certificate, for self-signed servers
+    #[arg(
+        long,
+        requires = "tls_server_name",
+        conflicts_with = "root_certificates"
+    )]
+    pub tls_unverified: bool,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -16,11 +32,74 @@ pub enum Command {
         key: String,
         #[arg(value_parser=parse_value)]
         value: rmemstore::types::MemstoreValue,
+        /// Expire the value after this long, like 30s, 500ms, 5m or 1h. Bare numbers are seconds.
+        #[arg(long, value_parser=parse_duration)]
+        ttl: Option<Duration>,
     },
     #[command(arg_required_else_help = true)]
     Get { key: String },
+    #[command(arg_required_else_help = true)]
+    Delete { key: String },
+    /// Get one field of a map value, by its path of nested map keys.
+    #[command(arg_required_else_help = true)]
+    GetField {
+        key: String,
+        #[arg(required = true)]
+        path: Vec<String>,
+    },
+    /// Set one field of a map value, creating maps along the path as needed.
+    #[command(arg_required_else_help = true)]
+    SetField {
+        key: String,
+        #[arg(value_parser=parse_value)]
+        value: rmemstore::types::MemstoreValue,
+        #[arg(required = true)]
+        path: Vec<String>,
+    },
+    /// Delete one field of a map value.
+    #[command(arg_required_else_help = true)]
+    DeleteField {
+        key: String,
+        #[arg(required = true)]
+        path: Vec<String>,
+    },
+    /// Add to an integer value, creating it if it is absent. Prints the new value.
+    #[command(arg_required_else_help = true)]
+    Increment {
+        key: String,
+        #[arg(default_value_t = 1, allow_negative_numbers = true)]
+        delta: i64,
+    },
+    /// Resize the server's cache, like 512mib or 2gib, through its admin address.
+    #[command(arg_required_else_help = true)]
+    SetSize {
+        #[arg(value_parser=parse_size)]
+        size: u64,
+        /// The server's --admin-address
+        #[arg(long, default_value = "127.0.0.1:9467", env = "ADMIN_HOST")]
+        admin_host: SocketAddr,
+    },
 }
 
 fn parse_value(s: &str) -> Result<rmemstore::types::MemstoreValue, serde_json::Error> {
     serde_json::from_str(s)
 }
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    parse_size::parse_size(s).map_err(|e| e.to_string())
+}
+
+fn parse_duration(s: &str) -> Result<Duration, ParseIntError> {
+    let s = s.trim();
+    if let Some(millis) = s.strip_suffix("ms") {
+        Ok(Duration::from_millis(millis.parse()?))
+    } else if let Some(seconds) = s.strip_suffix('s') {
+        Ok(Duration::from_secs(seconds.parse()?))
+    } else if let Some(minutes) = s.strip_suffix('m') {
+        Ok(Duration::from_secs(60 * minutes.parse::<u64>()?))
+    } else if let Some(hours) = s.strip_suffix('h') {
+        Ok(Duration::from_secs(60 * 60 * hours.parse::<u64>()?))
+    } else {
+        Ok(Duration::from_secs(s.parse()?))
+    }
+}
@@ -1,6 +1,9 @@
+use std::net::SocketAddr;
+
 use args::Args;
 use bytes::Buf;
 use clap::Parser;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 mod args;
 
@@ -18,8 +21,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let args::Command::SetSize { size, admin_host } = args.command {
+        return set_size(admin_host, size).await;
+    }
     let mut configuration = rmemstore::ConnectionConfiguration::default();
     configuration.max_message_size(32 * (1 << 20));
+    if let Some(server_name) = &args.tls_server_name {
+        match &args.root_certificates {
+            Some(root_certificates) => configuration
+                .tls_with_root_certificates(server_name, &std::fs::read(root_certificates)?)?,
+            None if args.tls_unverified => configuration.tls_unverified(server_name)?,
+            None => configuration.tls(server_name)?,
+        }
+    }
     let client = rmemstore::Client::connect(
         args.host
             .to_string()
@@ -30,40 +44,88 @@ async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     .await?;
 
     match args.command {
-        args::Command::Put { key, value } => {
-            client.put(key, value).await?;
+        args::Command::Put { key, value, ttl } => match ttl {
+            Some(ttl) => client.put_with_ttl(key, value, ttl).await?,
+            None => client.put(key, value).await?,
+        },
+        args::Command::Delete { key } => {
+            if !client.delete(key).await? {
+                eprintln!("miss");
+            }
         }
-        args::Command::Get { key } => {
-            let result = match client.get(key).await? {
-                Some(hit) => hit,
-                None => {
-                    eprintln!("miss");
-                    return Ok(());
-                }
-            };
-            match result {
-                rmemstore::types::MemstoreValue::Blob { value } => {
-                    match std::io::read_to_string(value.reader()) {
-                        Ok(v) => {
-                            println!("{v}");
-                        }
-                        Err(e) => {
-                            // cli doesn't support unstringable values
-                            eprintln!("unsupported value: {e:?}");
-                        }
-                    }
-                }
-                rmemstore::types::MemstoreValue::String { string: value } => {
-                    println!("{value}")
-                }
-                rmemstore::types::MemstoreValue::Map { map } => {
-                    serde_json::to_writer_pretty(std::io::stdout(), &map)
-                        .expect("must be printable");
-                    println!();
-                }
+        args::Command::Increment { key, delta } => {
+            println!("{}", client.increment(key, delta).await?);
+        }
+        args::Command::Get { key } => match client.get(key).await? {
+            Some(hit) => print_value(hit),
+            None => eprintln!("miss"),
+        },
+        args::Command::GetField { key, path } => match client.get_field(key, path).await? {
+            Some(hit) => print_value(hit),
+            None => eprintln!("miss"),
+        },
+        args::Command::SetField { key, value, path } => {
+            client.set_field(key, path, value).await?;
+        }
+        args::Command::DeleteField { key, path } => {
+            if !client.delete_field(key, path).await? {
+                eprintln!("miss");
             }
         }
+        args::Command::SetSize { .. } => unreachable!("set-size does not use the client"),
     }
 
     Ok(())
 }
+
+/// Resizing is an admin request, over http on the server's admin address.
+async fn set_size(admin_host: SocketAddr, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stream = tokio::net::TcpStream::connect(admin_host).await?;
+    stream
+        .write_all(
+            format!(
+                "POST /max-weight?size={size} HTTP/1.1\r\nHost: {admin_host}\r\nContent-Length: 0\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await?;
+    let mut response = String::new();
+    tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        stream.read_to_string(&mut response),
+    )
+    .await
+    .map_err(|_| "no response from the admin address")??;
+    let status = response.lines().next().unwrap_or_default();
+    if status.starts_with("HTTP/1.1 200") {
+        Ok(())
+    } else {
+        Err(format!("resize failed: {status}").into())
+    }
+}
+
+fn print_value(value: rmemstore::types::MemstoreValue) {
+    match value {
+        rmemstore::types::MemstoreValue::Blob { value } => {
+            match std::io::read_to_string(value.reader()) {
+                Ok(v) => {
+                    println!("{v}");
+                }
+                Err(e) => {
+                    // cli doesn't support unstringable values
+                    eprintln!("unsupported value: {e:?}");
+                }
+            }
+        }
+        rmemstore::types::MemstoreValue::String { string: value } => {
+            println!("{value}")
+        }
+        rmemstore::types::MemstoreValue::Map { map } => {
+            serde_json::to_writer_pretty(std::io::stdout(), &map).expect("must be printable");
+            println!();
+        }
+        rmemstore::types::MemstoreValue::Integer { integer } => {
+            println!("{integer}")
+        }
+    }
+}
@@ -48,7 +48,8 @@ impl Signals {
         Ok(signals)
     }
 
-    pub async fn wait_for_termination(self) {
+    /// Resolves upon sigint or sigterm. on_hangup runs for each sighup in the meantime.
+    pub async fn wait_for_termination(self, mut on_hangup: impl FnMut()) {
         let mut signals = self.signal_queue;
         loop {
             match signals.recv().await {
@@ -68,7 +69,8 @@ impl Signals {
                         log::info!("resolving future for {}", signal.name());
                     }
                     Signal::Sighup => {
-                        log::info!("nothing to do for {}", signal.name());
+                        log::info!("handling {}", signal.name());
+                        on_hangup();
                     }
                 },
                 None => {
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser, Debug)]
+#[clap(about = "Replay key accesses through k-cache and report hit ratios")]
+pub struct Args {
+    /// Cache capacities to simulate, in entries
+    #[arg(long, value_delimiter = ',', default_value = "1000,10000,100000")]
+    pub capacities: Vec<usize>,
+
+    /// Eviction policies to simulate
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "sieve,s3-fifo,lru"
+    )]
+    pub policies: Vec<Policy>,
+
+    #[command(subcommand)]
+    pub trace: Trace,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Trace {
+    /// Replay a trace file: one access per line, keyed by its first whitespace-separated
+    /// field. Blank lines and lines starting with # are skipped.
+    File { path: PathBuf },
+    /// Replay accesses to keys drawn from a Zipf distribution
+    Zipf {
+        /// Distinct keys to draw from
+        #[arg(long, default_value = "1000000")]
+        keys: usize,
+        /// Skew: higher makes the most popular keys more popular
+        #[arg(long, default_value = "0.9")]
+        exponent: f64,
+        #[arg(long, default_value = "10000000")]
+        requests: usize,
+        /// The same seed replays the same trace
+        #[arg(long, default_value = "0")]
+        seed: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    Sieve,
+    S3Fifo,
+    Lru,
+}
+
+impl Policy {
+    pub fn build<K>(self) -> k_cache::AnyPolicy<K> {
+        match self {
+            Policy::Sieve => k_cache::AnyPolicy::Sieve(Default::default()),
+            Policy::S3Fifo => k_cache::AnyPolicy::S3Fifo(Default::default()),
+            Policy::Lru => k_cache::AnyPolicy::Lru(Default::default()),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::Sieve => "sieve",
+            Policy::S3Fifo => "s3-fifo",
+            Policy::Lru => "lru",
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use args::{Args, Trace};
+use clap::Parser;
+
+mod args;
+mod simulation;
+mod trace;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let keys = match args.trace {
+        Trace::File { path } => trace::read(&path)?,
+        Trace::Zipf {
+            keys,
+            exponent,
+            requests,
+            seed,
+        } => trace::Zipf::new(keys, exponent).trace(requests, seed),
+    };
+    let mut distinct = keys.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    println!("{} requests for {} keys", keys.len(), distinct.len());
+
+    print!("{:>12}", "capacity");
+    for policy in &args.policies {
+        print!("{:>10}", policy.name());
+    }
+    println!();
+    for &capacity in &args.capacities {
+        print!("{capacity:>12}");
+        for &policy in &args.policies {
+            let ratio = simulation::hit_ratio(&keys, capacity, policy);
+            print!("{:>9.2}%", 100.0 * ratio);
+        }
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::hash::RandomState;
+
+use k_cache::{AnyPolicy, Cache, DefaultLifecycle, One};
+
+use crate::args::Policy;
+
+/// Replay keys through a cache of capacity entries. Each access is a get, followed by a put
+/// when it misses. Returns the fraction of gets that hit.
+pub fn hit_ratio(keys: &[u64], capacity: usize, policy: Policy) -> f64 {
+    if keys.is_empty() {
+        return 0.0;
+    }
+    let mut cache: Cache<u64, (), RandomState, One, DefaultLifecycle, AnyPolicy<u64>> =
+        Cache::new_with_policy(
+            RandomState::new(),
+            capacity,
+            DefaultLifecycle,
+            policy.build(),
+        );
+    for &key in keys {
+        if cache.get(&key).is_none() {
+            cache.put(key, ());
+        }
+    }
+    let stats = cache.stats();
+    stats.hits as f64 / (stats.hits + stats.misses) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::trace::Zipf;
+
+    /// Hit ratios on a fixed trace, with some slack below the ratios measured when these were
+    /// written. An eviction change that drops below them is a regression.
+    #[test]
+    fn test_hit_ratios_do_not_regress() {
+        let keys = Zipf::new(100_000, 0.9).trace(300_000, 0);
+        for (policy, capacity, floor) in [
+            (Policy::Sieve, 1_000, 0.34),
+            (Policy::Sieve, 10_000, 0.59),
+            (Policy::S3Fifo, 1_000, 0.43),
+            (Policy::S3Fifo, 10_000, 0.63),
+            (Policy::Lru, 1_000, 0.33),
+            (Policy::Lru, 10_000, 0.58),
+        ] {
+            let ratio = hit_ratio(&keys, capacity, policy);
+            assert!(
+                floor <= ratio,
+                "{} at {capacity}: {ratio} < {floor}",
+                policy.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_everything_fits() {
+        let keys: Vec<u64> = (0..100).cycle().take(1000).collect();
+        for policy in [Policy::Sieve, Policy::S3Fifo, Policy::Lru] {
+            assert_eq!(hit_ratio(&keys, 100, policy), 0.9);
+        }
+    }
+}
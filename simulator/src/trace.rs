@@ -0,0 +1,91 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, BufRead},
+    path::Path,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Read a trace file into keys. Numeric keys are used as they are, and other keys are hashed.
+pub fn read(path: &Path) -> io::Result<Vec<u64>> {
+    let file = io::BufReader::new(std::fs::File::open(path)?);
+    let mut keys = Vec::new();
+    for line in file.lines() {
+        let line = line?;
+        let Some(key) = line.split_whitespace().next() else {
+            continue;
+        };
+        if key.starts_with('#') {
+            continue;
+        }
+        keys.push(key.parse().unwrap_or_else(|_| {
+            // DefaultHasher::new is unkeyed, so a trace hashes the same way every run.
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            hasher.finish()
+        }));
+    }
+    Ok(keys)
+}
+
+/// Samples keys 0..n with probability proportional to 1 / (key + 1)^exponent, so key 0 is the
+/// most popular.
+pub struct Zipf {
+    cumulative: Vec<f64>,
+}
+
+impl Zipf {
+    pub fn new(keys: usize, exponent: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (0..keys)
+            .map(|rank| {
+                total += 1.0 / ((rank + 1) as f64).powf(exponent);
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> u64 {
+        let total = self.cumulative.last().copied().unwrap_or_default();
+        let target = rng.gen::<f64>() * total;
+        self.cumulative.partition_point(|&sum| sum < target) as u64
+    }
+
+    /// A trace of requests drawn from this distribution. The same seed gives the same trace.
+    pub fn trace(&self, requests: usize, seed: u64) -> Vec<u64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..requests).map(|_| self.sample(&mut rng)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zipf_is_skewed_and_reproducible() {
+        let zipf = Zipf::new(1000, 1.0);
+        let trace = zipf.trace(100_000, 7);
+        assert_eq!(trace, zipf.trace(100_000, 7));
+        assert!(trace.iter().all(|&key| key < 1000));
+
+        let count = |key| trace.iter().filter(|&&k| k == key).count();
+        assert!(count(1) < count(0));
+        assert!(count(100) < count(1));
+    }
+
+    #[test]
+    fn test_read() {
+        let path = std::env::temp_dir().join(format!("trace-{}", std::process::id()));
+        std::fs::write(&path, "# a comment\n12 get\n\nuser:1\n12\nuser:1 put 3\n")
+            .expect("can write the trace");
+        let keys = read(&path).expect("can read the trace");
+        std::fs::remove_file(&path).expect("can remove the trace");
+
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0], 12);
+        assert_eq!(keys[2], 12);
+        assert_eq!(keys[1], keys[3]);
+    }
+}